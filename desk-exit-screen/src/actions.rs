@@ -7,21 +7,29 @@ use dbus::blocking::Connection;
 use gdk::keys::{constants as keys, Key};
 use gdk::keyval_from_name;
 use glib::translate::from_glib;
+use log::{debug, warn};
 
-use desk_logind::Logind;
+use desk_logind::{Logind, PowerAction};
 
-use crate::config::{Config, CustomAction};
+use crate::config::{detect_quit_command, Config, CustomAction};
 
 /// Action to show in the exit screen
 pub struct Action {
     key: Key,
     icon: String,
     description: String,
+    confirm: bool,
+    available: bool,
+    /// Button size for this action, if it overrides the configured `button_size`
+    size: Option<i32>,
+    /// For built-in power actions, which logind operation this performs
+    power: Option<PowerAction>,
     run: Box<dyn Fn() -> anyhow::Result<()>>,
 }
 
 impl Action {
-    /// Icon displayed for this action as a button in the exit screen
+    /// Icon displayed for this action as a button in the exit screen. This is either an icon theme
+    /// name, or an absolute path or `file://` URI to an image file.
     pub fn icon(&self) -> &str {
         &self.icon
     }
@@ -31,6 +39,21 @@ impl Action {
         &self.description
     }
 
+    /// Whether to ask the user before running this action
+    pub fn confirm(&self) -> bool {
+        self.confirm
+    }
+
+    /// Whether this action can run on this system. Unavailable actions are shown, but disabled.
+    pub fn available(&self) -> bool {
+        self.available
+    }
+
+    /// Width and height of this action's button, if it has its own size rather than the default
+    pub fn size(&self) -> Option<i32> {
+        self.size
+    }
+
     /// Run this action
     pub fn run(&self) -> anyhow::Result<()> {
         (self.run)()
@@ -50,9 +73,10 @@ impl Actions {
             .flat_map(move |act| Some(act).zip(self.actions.get(act)))
     }
 
-    /// Find the action with the given keyboard shortcut, if one is defined
+    /// Find the displayed action with the given keyboard shortcut, if one is defined. Actions
+    /// that aren't in the configured order don't have a button, so their shortcuts are ignored.
     pub fn find_by_key(&self, key: Key) -> Option<&Action> {
-        self.actions.values().find(|act| act.key == key)
+        self.iter().map(|(_, act)| act).find(|act| act.key == key)
     }
 
     pub fn get(&self, name: &str) -> &Action {
@@ -69,33 +93,66 @@ pub fn build_actions(config: Config) -> Actions {
     );
     actions.insert(
         "suspend".to_string(),
-        static_action(
+        power_action(
             keys::s,
             "system-suspend",
             "Put the computer to sleep",
-            suspend,
+            PowerAction::Suspend,
         ),
     );
     actions.insert(
         "hibernate".to_string(),
-        static_action(
+        power_action(
             keys::h,
             "system-hibernate",
             "Hibernate the computer",
-            hibernate,
+            PowerAction::Hibernate,
+        ),
+    );
+    actions.insert(
+        "hybrid-sleep".to_string(),
+        power_action(
+            keys::y,
+            "system-suspend-hibernate",
+            "Put the computer to sleep, keeping a hibernation image in case power is lost",
+            PowerAction::HybridSleep,
+        ),
+    );
+    actions.insert(
+        "suspend-then-hibernate".to_string(),
+        power_action(
+            keys::t,
+            // A clock, for hibernating after a delay. Hybrid sleep has the suspend/hibernate icon.
+            "appointment-soon",
+            "Put the computer to sleep, then hibernate it after a while",
+            PowerAction::SuspendThenHibernate,
         ),
     );
     actions.insert(
         "reboot".to_string(),
-        static_action(keys::r, "system-restart", "Restart the computer", restart),
+        power_action(
+            keys::r,
+            "system-restart",
+            "Restart the computer",
+            PowerAction::Reboot,
+        ),
     );
     actions.insert(
-        "shutdown".to_string(),
+        "firmware-setup".to_string(),
         static_action(
+            keys::f,
+            "preferences-system",
+            "Restart into the firmware setup screen",
+            restart_to_firmware_setup,
+        ),
+    );
+    actions.insert(
+        "shutdown".to_string(),
+        power_action(
             keys::p,
             "system-shutdown",
             "Shut the computer off",
-            shut_down,
+            PowerAction::PowerOff,
         ),
     );
 
@@ -104,15 +161,22 @@ pub fn build_actions(config: Config) -> Actions {
         quit_command,
         order,
         actions: custom_actions,
+        confirm,
+        ..
     } = config;
 
-    if let Some(quit_command) = quit_command {
+    if let Some(quit_command) = quit_command.or_else(detect_quit_command) {
+        debug!("Quitting with {}", quit_command);
         actions.insert(
             "quit".to_string(),
             Action {
                 key: keys::q,
                 icon: "system-log-out".to_string(),
                 description: "Log out".to_string(),
+                confirm: false,
+                available: true,
+                size: None,
+                power: None,
                 run: exec_action(quit_command),
             },
         );
@@ -124,6 +188,8 @@ pub fn build_actions(config: Config) -> Actions {
             icon,
             description,
             command,
+            confirm,
+            size,
             ..
         } = custom;
         actions.insert(
@@ -132,14 +198,59 @@ pub fn build_actions(config: Config) -> Actions {
                 key,
                 icon,
                 description,
+                confirm,
+                available: true,
+                size,
+                power: None,
                 run: exec_action(command),
             },
         );
     }
 
+    for name in confirm {
+        match actions.get_mut(&name) {
+            Some(action) => action.confirm = true,
+            None => warn!("Cannot confirm unknown action {}", name),
+        }
+    }
+
+    disable_unavailable(&mut actions);
+
     Actions { actions, order }
 }
 
+/// Disables built-in power actions that logind says can't be done, so users find out up front
+/// rather than when the action fails
+fn disable_unavailable(actions: &mut HashMap<String, Action>) {
+    let conn = match Connection::new_system() {
+        Ok(conn) => conn,
+        Err(e) => {
+            warn!(
+                "Could not connect to D-Bus to check available actions: {}",
+                e
+            );
+            return;
+        }
+    };
+    let logind = Logind::new(&conn);
+
+    for (name, action) in actions.iter_mut() {
+        let capability = match (name.as_str(), action.power) {
+            ("firmware-setup", _) => logind.can_reboot_to_firmware_setup(),
+            (_, Some(power)) => logind.can_power(power),
+            _ => continue,
+        };
+        match capability {
+            Ok(capability) if !capability.is_available() => {
+                debug!("Disabling {} action ({})", name, capability);
+                action.available = false;
+            }
+            Ok(_) => (),
+            Err(e) => warn!("Could not check if {} is available: {}", name, e),
+        }
+    }
+}
+
 /// Helper for defining built-in actions
 fn static_action(
     key: Key,
@@ -151,10 +262,35 @@ fn static_action(
         key,
         icon: icon.to_string(),
         description: description.to_string(),
+        confirm: false,
+        available: true,
+        size: None,
+        power: None,
         run: Box::new(run),
     }
 }
 
+/// Helper for defining built-in actions that suspend, reboot, etc. the system through logind
+fn power_action(key: Key, icon: &str, description: &str, action: PowerAction) -> Action {
+    Action {
+        key,
+        icon: icon.to_string(),
+        description: description.to_string(),
+        confirm: false,
+        available: true,
+        size: None,
+        power: Some(action),
+        run: Box::new(move || {
+            let conn = Connection::new_system().context("Could not connect to D-Bus")?;
+            let logind = Logind::new(&conn);
+            logind
+                .power(action, true)
+                .with_context(|| format!("Error performing {}", action))?;
+            Ok(())
+        }),
+    }
+}
+
 /// Helper that creates an action function to run the given command in the user's default shell
 fn exec_action(command: String) -> Box<dyn Fn() -> anyhow::Result<()>> {
     let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
@@ -178,13 +314,6 @@ fn exec_action(command: String) -> Box<dyn Fn() -> anyhow::Result<()>> {
     })
 }
 
-fn suspend() -> anyhow::Result<()> {
-    let conn = Connection::new_system().context("Could not connect to D-Bus")?;
-    let logind = Logind::new(&conn);
-    logind.suspend(true).context("Error suspending system")?;
-    Ok(())
-}
-
 fn lock() -> anyhow::Result<()> {
     let conn = Connection::new_system().context("Could not connect to D-Bus")?;
     let logind = Logind::new(&conn);
@@ -195,25 +324,18 @@ fn lock() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn hibernate() -> anyhow::Result<()> {
-    let conn = Connection::new_system().context("Could not connect to D-Bus")?;
-    let logind = Logind::new(&conn);
-    logind.hibernate(true).context("Error hibernating system")?;
-    Ok(())
-}
-
-fn restart() -> anyhow::Result<()> {
-    let conn = Connection::new_system().context("Could not connect to D-Bus")?;
-    let logind = Logind::new(&conn);
-    logind.reboot(true).context("Error rebooting system")?;
-    Ok(())
-}
-
-fn shut_down() -> anyhow::Result<()> {
+fn restart_to_firmware_setup() -> anyhow::Result<()> {
     let conn = Connection::new_system().context("Could not connect to D-Bus")?;
     let logind = Logind::new(&conn);
     logind
-        .power_off(true)
-        .context("Error shutting down system")?;
+        .set_reboot_to_firmware_setup(true)
+        .context("Error requesting firmware setup on next boot")?;
+    if let Err(e) = logind.reboot(true) {
+        // Otherwise, the next ordinary reboot would unexpectedly go into firmware setup
+        if let Err(clear_err) = logind.set_reboot_to_firmware_setup(false) {
+            warn!("Could not clear firmware setup request: {}", clear_err);
+        }
+        return Err(e).context("Error rebooting system");
+    }
     Ok(())
 }
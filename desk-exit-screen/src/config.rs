@@ -1,25 +1,47 @@
 use std::collections::HashMap;
+use std::env;
 
 use serde::{Deserialize, Serialize};
 
-// It might be worth adding support for specific window managers at some point
-// (for example, if using i3, infer `i3-msg exit` for `quit_command`)
-
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     /// Command to quit the window manager or desktop environment. For example, when using i3, this
-    /// would be `i3-msg exit`
+    /// would be `i3-msg exit`. If unset, it's inferred for some common window managers (see
+    /// [`detect_quit_command`]).
     #[serde(default)]
     pub quit_command: Option<String>,
 
     /// Order to display actions in, by name. Built-in actions are `lock`, `quit`, `suspend`,
-    /// `hibernate`, `reboot`, and `shutdown`.
+    /// `hibernate`, `hybrid-sleep`, `suspend-then-hibernate`, `reboot`, `firmware-setup`, and
+    /// `shutdown`. Only `hybrid-sleep`, `suspend-then-hibernate`, and `firmware-setup` are left
+    /// out of the default order.
     #[serde(default = "default_action_order")]
     pub order: Vec<String>,
 
     /// Additional custom actions to display
     #[serde(default)]
     pub actions: HashMap<String, CustomAction>,
+
+    /// Show each action's description as text under its button
+    #[serde(default)]
+    pub show_labels: bool,
+
+    /// Maximum number of buttons per row. All buttons are shown in one row if unset.
+    #[serde(default)]
+    pub columns: Option<usize>,
+
+    /// Built-in actions to ask for confirmation before running, by name. Custom actions set
+    /// `confirm` on the action itself.
+    #[serde(default)]
+    pub confirm: Vec<String>,
+
+    /// Close the exit screen after this many seconds without any keyboard or mouse input
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+
+    /// Width and height of each button, in pixels
+    #[serde(default = "default_button_size")]
+    pub button_size: i32,
 }
 
 /// Default action order. Used both when the config file is missing and to provide a default if
@@ -35,22 +57,58 @@ fn default_action_order() -> Vec<String> {
     ]
 }
 
+fn default_button_size() -> i32 {
+    400
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
             quit_command: None,
             order: default_action_order(),
             actions: HashMap::new(),
+            show_labels: false,
+            columns: None,
+            confirm: Vec::new(),
+            timeout_secs: None,
+            button_size: default_button_size(),
         }
     }
 }
 
+/// Picks a quit command based on the running window manager or desktop environment, if it's one we
+/// know how to quit.
+pub fn detect_quit_command() -> Option<String> {
+    // Sway and i3 advertise their IPC sockets, which is more reliable than XDG_CURRENT_DESKTOP
+    if env::var_os("SWAYSOCK").is_some() {
+        return Some("swaymsg exit".to_string());
+    }
+    if env::var_os("I3SOCK").is_some() {
+        return Some("i3-msg exit".to_string());
+    }
+
+    // XDG_CURRENT_DESKTOP is a colon-separated list, most specific first
+    let desktops = env::var("XDG_CURRENT_DESKTOP").ok()?;
+    desktops.split(':').find_map(|desktop| {
+        let command = match desktop.to_lowercase().as_str() {
+            "sway" => "swaymsg exit",
+            "i3" => "i3-msg exit",
+            "gnome" => "gnome-session-quit --logout --no-prompt",
+            "kde" => "qdbus org.kde.ksmserver /KSMServer logout 0 0 0",
+            "xfce" => "xfce4-session-logout --logout",
+            "mate" => "mate-session-save --logout",
+            _ => return None,
+        };
+        Some(command.to_string())
+    })
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct CustomAction {
     /// Name of the key that triggers this action
     pub key: String,
 
-    /// Name of the button icon to use
+    /// Name of the button icon to use, or an absolute path or `file://` URI to an image
     pub icon: String,
 
     /// Description of the action, used for accessibility labels
@@ -58,4 +116,12 @@ pub struct CustomAction {
 
     /// Command to run (via shell)
     pub command: String,
+
+    /// Ask for confirmation before running the command
+    #[serde(default)]
+    pub confirm: bool,
+
+    /// Width and height of this action's button, in pixels. Defaults to `button_size`.
+    #[serde(default)]
+    pub size: Option<i32>,
 }
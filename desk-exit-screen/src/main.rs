@@ -1,77 +1,206 @@
-#![feature(const_fn)]
-
 use std::io::ErrorKind;
 use std::env;
-use std::sync::Arc;
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, bail, Context};
 use atk::prelude::*;
 use directories::ProjectDirs;
 use env_logger::Env;
-use gdk::keys::constants as keys;
-use gdk::{Screen, WindowTypeHint};
+use gdk::keys::{constants as keys, Key};
+use gdk::{Display, EventMask, EventType, Screen, WindowTypeHint};
+use gdk_pixbuf::Pixbuf;
 use gio::prelude::*;
-use glib::clone;
+use glib::{clone, Continue};
 use gtk::prelude::*;
 use gtk::{
-    Application, Button, CssProvider, IconLookupFlags, IconTheme, Image, Orientation, StyleContext,
-    Window, WindowType,
+    Application, Button, ButtonsType, CssProvider, DialogFlags, DirectionType, Grid,
+    IconLookupFlags, IconTheme, Image, Label, MessageDialog, MessageType, Orientation,
+    ResponseType, StyleContext, Window, WindowType,
 };
-use log::{error, debug};
+use log::{error, debug, info, warn};
+use structopt::StructOpt;
 
 mod actions;
 mod config;
+mod wayland;
 
-use crate::actions::build_actions;
+use crate::actions::{build_actions, Action, Actions};
 use crate::config::Config;
 
 const STYLE: &str = include_str!("desk-exit-screen.css");
 
-const BUTTON_SIZE: i32 = 400;
+/// Command-line flags
+#[derive(StructOpt)]
+struct Args {
+    /// Configuration file to use instead of `~/.config/desk/exit-screen.toml`
+    #[structopt(long, value_name = "path", parse(from_os_str))]
+    config: Option<PathBuf>,
+}
+
+/// Layout settings from the configuration, which apply to every exit screen window
+#[derive(Clone, Copy)]
+struct Layout {
+    show_labels: bool,
+    columns: Option<usize>,
+    button_size: i32,
+}
 
-fn build_ui(app: &Application) -> anyhow::Result<()> {
-    let config = load_config()?;
-    let actions = Arc::new(build_actions(config));
+fn build_ui(app: &Application, config_file: Option<&Path>) -> anyhow::Result<()> {
+    let icon_theme = IconTheme::get_default().ok_or_else(|| anyhow!("No default icon theme"))?;
+    let windows = Rc::new(RefCell::new(create_windows(app, &icon_theme, config_file)?));
+    let config_file = config_file.map(Path::to_path_buf);
+
+    // On SIGHUP, swap in new windows built from the current config and stylesheet. The old
+    // windows are only closed once the new ones are up, so the application doesn't exit in between.
+    glib::unix_signal_add_local(
+        libc::SIGHUP,
+        clone!(@weak app => @default-return Continue(false), move || {
+            info!("Reloading configuration");
+            match create_windows(&app, &icon_theme, config_file.as_deref()) {
+                Ok(new_windows) => {
+                    for window in windows.replace(new_windows) {
+                        window.close();
+                    }
+                }
+                Err(e) => error!("Could not reload configuration: {:#}", e),
+            }
+            Continue(true)
+        }),
+    );
+
+    Ok(())
+}
 
+/// Creates and shows an exit screen window on each monitor, based on the current configuration
+fn create_windows(
+    app: &Application,
+    icon_theme: &IconTheme,
+    config_file: Option<&Path>,
+) -> anyhow::Result<Vec<Window>> {
+    let config = load_config(config_file)?;
+    let layout = Layout {
+        show_labels: config.show_labels,
+        columns: config.columns,
+        button_size: config.button_size,
+    };
+    let timeout_secs = config.timeout_secs;
+    let actions = Rc::new(build_actions(config));
+    let style = Rc::new(Style::load()?);
+
+    // Shared by every window, and any confirmation dialogs they open
+    let last_activity = Rc::new(Cell::new(Instant::now()));
+
+    let display = Display::get_default().ok_or_else(|| anyhow!("No default display"))?;
+    let windows = (0..display.get_n_monitors())
+        .map(|monitor| {
+            create_window(
+                app,
+                &actions,
+                icon_theme,
+                &style,
+                layout,
+                monitor,
+                &last_activity,
+            )
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    if let Some(timeout_secs) = timeout_secs {
+        close_when_inactive(
+            app,
+            &windows,
+            last_activity,
+            Duration::from_secs(timeout_secs),
+        );
+    }
+
+    Ok(windows)
+}
+
+/// Creates and shows the exit screen window for one monitor
+fn create_window(
+    app: &Application,
+    actions: &Rc<Actions>,
+    icon_theme: &IconTheme,
+    style: &Rc<Style>,
+    layout: Layout,
+    monitor: i32,
+    last_activity: &Rc<Cell<Instant>>,
+) -> anyhow::Result<Window> {
     let window = Window::new(WindowType::Toplevel);
     app.add_window(&window);
     window.set_widget_name("exit-window"); // used in CSS
+    track_activity(&window, last_activity);
 
     window.connect_key_press_event(
-        clone!(@strong actions, @weak app => @default-return Inhibit(false), move |_window, event| {
+        clone!(@strong actions, @strong last_activity, @weak app => @default-return Inhibit(false), move |window, event| {
+            let key = event.get_keyval();
             // Quit whenever Escape or a known action key is pressed
-            if event.get_keyval() == keys::Escape {
+            if key == keys::Escape {
                 app.quit();
-            } else if let Some(action) = actions.find_by_key(event.get_keyval()) {
-                if let Err(e) = action.run() {
-                    error!("Action failed: {}", e);
+            } else if let Some(direction) = focus_direction(&key) {
+                window.child_focus(direction);
+                return Inhibit(true);
+            } else if key == keys::Return || key == keys::KP_Enter {
+                // Enter runs whichever action's button is focused
+                if let Some(button) = window.get_focus().and_then(|w| w.downcast::<Button>().ok()) {
+                    button.clicked();
+                    return Inhibit(true);
                 }
-                app.quit();
+            } else if let Some(action) = actions.find_by_key(key) {
+                trigger(&app, window, action, &last_activity);
             }
 
             Inhibit(false)
         }),
     );
 
-    let container = gtk::Box::new(Orientation::Horizontal, 0);
-    container.set_homogeneous(true); // This makes all children the same size
+    let container = Grid::new();
+    // This makes all children the same size
+    container.set_row_homogeneous(true);
+    container.set_column_homogeneous(true);
 
-    let icon_theme = IconTheme::get_default().ok_or_else(|| anyhow!("No default icon theme"))?;
+    // Without a column limit, everything goes in one row
+    let columns = layout
+        .columns
+        .filter(|&columns| columns > 0)
+        .unwrap_or_else(|| actions.iter().count());
+
+    for (index, (name, action)) in actions.iter().enumerate() {
+        let column = (index % columns) as i32;
+        let row = (index / columns) as i32;
 
-    for (name, action) in actions.iter() {
-        let button = create_button(&icon_theme, action.icon())?;
+        let size = action.size().unwrap_or(layout.button_size);
+        let button = create_button(icon_theme, action.icon(), size)?;
+        // So stylesheets can target specific actions, such as `.action-shutdown`
+        button
+            .get_style_context()
+            .add_class(&format!("action-{}", name));
         let name = name.to_string();
-        button.connect_clicked(clone!(@strong actions, @weak app => move |_| {
-            if let Err(e) = actions.get(&name).run() {
-                error!("Action failed: {}", e);
-            }
-            app.quit();
-        }));
+        button.connect_clicked(
+            clone!(@strong actions, @strong last_activity, @weak app, @weak window => move |_| {
+                trigger(&app, &window, actions.get(&name), &last_activity);
+            }),
+        );
         if let Some(a11y) = button.get_accessible() {
             a11y.set_description(action.description());
         }
-        // TODO: may want to show descriptions on-screen as well
-        container.pack_start(&button, false, false, 0);
+        button.set_tooltip_text(Some(action.description()));
+        button.set_sensitive(action.available());
+
+        if layout.show_labels {
+            let item = gtk::Box::new(Orientation::Vertical, 0);
+            item.pack_start(&button, false, false, 0);
+            let label = Label::new(Some(action.description()));
+            label.get_style_context().add_class("action-label");
+            item.pack_start(&label, false, false, 0);
+            container.attach(&item, column, row, 1, 1);
+        } else {
+            container.attach(&button, column, row, 1, 1);
+        }
     }
 
     // Put the container in more boxes so it doesn't expand
@@ -81,81 +210,294 @@ fn build_ui(app: &Application) -> anyhow::Result<()> {
     hbox.pack_start(&vbox, true, false, 0);
     window.add(&hbox);
 
+    // On Wayland, the compositor places the window, so the X11 positioning tricks don't apply
+    let layer_shell = wayland::init_layer_shell(&window);
+
     if let Some(ref screen) = window.get_screen() {
-        configure_screen(&window, screen)?;
+        configure_screen(&window, screen, style, monitor, layer_shell)?;
     }
 
-    window.connect_screen_changed(move |window, screen| {
+    window.connect_screen_changed(clone!(@strong style => move |window, screen| {
         if let Some(screen) = screen {
-            if let Err(e) = configure_screen(window, screen) {
+            if let Err(e) = configure_screen(window, screen, &style, monitor, layer_shell) {
                 error!("Could not adjust to screen change: {}", e);
             }
         }
-    });
+    }));
 
     window.set_decorated(false);
     window.set_skip_taskbar_hint(true);
     window.set_skip_pager_hint(true);
-    window.set_type_hint(WindowTypeHint::Desktop);
-    window.set_keep_above(true);
+    if !layer_shell {
+        window.set_type_hint(WindowTypeHint::Desktop);
+        window.set_keep_above(true);
+    }
     window.show_all();
-    window.stick();
+    if !layer_shell {
+        window.stick();
+    }
     // window.fullscreen();
-    Ok(())
+    Ok(window)
+}
+
+/// Maps arrow keys to the direction to move button focus in.
+fn focus_direction(key: &Key) -> Option<DirectionType> {
+    if *key == keys::Left || *key == keys::KP_Left {
+        Some(DirectionType::Left)
+    } else if *key == keys::Right || *key == keys::KP_Right {
+        Some(DirectionType::Right)
+    } else if *key == keys::Up || *key == keys::KP_Up {
+        Some(DirectionType::Up)
+    } else if *key == keys::Down || *key == keys::KP_Down {
+        Some(DirectionType::Down)
+    } else {
+        None
+    }
+}
+
+/// Records the time of any keyboard or mouse input on `window` in `last_activity`.
+fn track_activity<W: IsA<gtk::Widget>>(window: &W, last_activity: &Rc<Cell<Instant>>) {
+    window.add_events(
+        EventMask::KEY_PRESS_MASK
+            | EventMask::BUTTON_PRESS_MASK
+            | EventMask::POINTER_MOTION_MASK
+            | EventMask::SCROLL_MASK,
+    );
+    window.connect_event(clone!(@strong last_activity => move |_window, event| {
+        if matches!(
+            event.get_event_type(),
+            EventType::KeyPress | EventType::ButtonPress | EventType::MotionNotify | EventType::Scroll
+        ) {
+            last_activity.set(Instant::now());
+        }
+        Inhibit(false)
+    }));
 }
 
-/// Creates a new button with the given icon, scaled to `BUTTON_SIZE`.
-fn create_button(icon_theme: &IconTheme, icon_name: &str) -> anyhow::Result<Button> {
+/// Quits once there's been no keyboard or mouse input on any of the windows, or their dialogs, for
+/// `timeout`.
+fn close_when_inactive(
+    app: &Application,
+    windows: &[Window],
+    last_activity: Rc<Cell<Instant>>,
+    timeout: Duration,
+) {
+    // Shared with the timer itself, so whichever of it and the windows goes first removes the id
+    let source = Rc::new(Cell::new(None));
+
+    let id = glib::timeout_add_seconds_local(
+        1,
+        clone!(@weak app, @strong source => @default-return Continue(false), move || {
+            if last_activity.get().elapsed() >= timeout {
+                debug!("No input for {:?}, closing", timeout);
+                // Returning false removes the source, so it mustn't be removed again
+                source.set(None);
+                app.quit();
+                Continue(false)
+            } else {
+                Continue(true)
+            }
+        }),
+    );
+    source.set(Some(id));
+
+    // Stop the timer if the windows are replaced by a config reload. They're all replaced at once,
+    // so watching one is enough.
+    if let Some(window) = windows.first() {
+        window.connect_destroy(move |_| {
+            if let Some(id) = source.take() {
+                glib::source_remove(id);
+            }
+        });
+    }
+}
+
+/// Runs an action and quits, unless the action needs confirmation and the user declines. Declining
+/// goes back to the exit screen.
+fn trigger(app: &Application, window: &Window, action: &Action, last_activity: &Rc<Cell<Instant>>) {
+    // Shortcut keys still reach disabled actions
+    if !action.available() {
+        return;
+    }
+
+    if action.confirm() {
+        let dialog = MessageDialog::new(
+            Some(window),
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            MessageType::Question,
+            ButtonsType::YesNo,
+            &format!("{}?", action.description()),
+        );
+        track_activity(&dialog, last_activity);
+        // Escape closes the dialog with ResponseType::DeleteEvent, which counts as declining
+        let response = dialog.run();
+        dialog.close();
+        if response != ResponseType::Yes {
+            return;
+        }
+    }
+
+    if let Err(e) = action.run() {
+        error!("Action failed: {}", e);
+    }
+    app.quit();
+}
+
+/// Creates a new button with the given icon, scaled to `size`.
+fn create_button(icon_theme: &IconTheme, icon_name: &str, size: i32) -> anyhow::Result<Button> {
     // Have to load the icon image directly to make it the right size
-    let icon = icon_theme
-        .load_icon(icon_name, BUTTON_SIZE, IconLookupFlags::empty())
-        .with_context(|| format!("Could not load icon {}", icon_name))?
-        .ok_or_else(|| anyhow!("Icon {} not found", icon_name))?
-        .copy() // GTK docs say to do this so the rest of the icon theme can be freed if needed
-        .ok_or_else(|| anyhow!("Could not copy icon {}", icon_name))?;
+    let icon = match icon_file(icon_name) {
+        Some(path) => Pixbuf::from_file_at_scale(&path, size, size, true)
+            .with_context(|| format!("Could not load icon {}", path.display()))?,
+        None => icon_theme
+            .load_icon(icon_name, size, IconLookupFlags::empty())
+            .with_context(|| format!("Could not load icon {}", icon_name))?
+            .ok_or_else(|| anyhow!("Icon {} not found", icon_name))?
+            .copy() // GTK docs say to do this so the rest of the icon theme can be freed if needed
+            .ok_or_else(|| anyhow!("Could not copy icon {}", icon_name))?,
+    };
 
     let button = Button::new();
-    button.set_size_request(BUTTON_SIZE, BUTTON_SIZE);
+    button.set_size_request(size, size);
     let image = Image::from_pixbuf(Some(&icon));
     button.set_image(Some(&image));
     Ok(button)
 }
 
-/// Configure a screen for displaying the exit window
-fn configure_screen(window: &Window, screen: &Screen) -> anyhow::Result<()> {
+/// If `icon` refers to an image file rather than an icon theme name, returns the file's path.
+fn icon_file(icon: &str) -> Option<PathBuf> {
+    if icon.starts_with("file://") {
+        gio::File::new_for_uri(icon).get_path()
+    } else {
+        let path = Path::new(icon);
+        if path.is_absolute() {
+            Some(path.to_path_buf())
+        } else {
+            None
+        }
+    }
+}
+
+/// Configure a screen for displaying the exit window, covering the given monitor. If `layer_shell`
+/// is set, the window is a Wayland layer-shell surface, which is sized by the compositor.
+fn configure_screen(
+    window: &Window,
+    screen: &Screen,
+    style: &Style,
+    monitor: i32,
+    layer_shell: bool,
+) -> anyhow::Result<()> {
     // Updates the window's GDK visual, which is required for transparency to work correctly.
     window.set_visual(screen.get_rgba_visual().as_ref());
 
     let monitor = screen
         .get_display()
-        .get_primary_monitor()
-        .ok_or_else(|| anyhow!("Primary monitor does not exist"))?;
-    let workarea = monitor.get_workarea();
-    window.resize(workarea.width, workarea.height);
-    // TODO: I'm not sure if it's polybar or i3, but the window is shifted down a couple pixels from
-    //       covering the whole screen. This move is a workaround to fix it for now :/
-    window.move_(0, -2);
-
-    // Since GTK objects aren't thread-safe, there's no way to have a shared CSS provider
-    let provider = CssProvider::new();
-    provider
-        .load_from_data(STYLE.as_bytes())
-        .context("Could not load CSS")?;
-    StyleContext::add_provider_for_screen(
-        screen,
-        &provider,
-        gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
-    );
+        .get_monitor(monitor)
+        .ok_or_else(|| anyhow!("Monitor {} does not exist", monitor))?;
+    if layer_shell {
+        wayland::set_monitor(window, &monitor);
+    } else {
+        let workarea = monitor.get_workarea();
+        window.resize(workarea.width, workarea.height);
+        // TODO: I'm not sure if it's polybar or i3, but the window is shifted down a couple pixels
+        //       from covering the whole screen. This move is a workaround to fix it for now :/
+        window.move_(workarea.x, workarea.y - 2);
+    }
+
+    style.add_to_screen(screen);
     Ok(())
 }
 
-fn load_config() -> anyhow::Result<Config> {
-    let dirs = match ProjectDirs::from("com.bennavetta", "", "desk") {
-        Some(dirs) => dirs,
+/// The built-in and user stylesheets, which are parsed once and shared by every window
+struct Style {
+    builtin: CssProvider,
+    user: Option<CssProvider>,
+    /// Screens the stylesheets have been added to
+    screens: RefCell<Vec<Screen>>,
+}
+
+impl Style {
+    fn load() -> anyhow::Result<Style> {
+        let builtin = CssProvider::new();
+        builtin
+            .load_from_data(STYLE.as_bytes())
+            .context("Could not load CSS")?;
+        Ok(Style {
+            builtin,
+            user: load_user_style()?,
+            screens: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Adds the stylesheets to `screen`, unless they're already there
+    fn add_to_screen(&self, screen: &Screen) {
+        let mut screens = self.screens.borrow_mut();
+        if screens.contains(screen) {
+            return;
+        }
+
+        StyleContext::add_provider_for_screen(
+            screen,
+            &self.builtin,
+            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
+        if let Some(ref user) = self.user {
+            StyleContext::add_provider_for_screen(screen, user, gtk::STYLE_PROVIDER_PRIORITY_USER);
+        }
+        screens.push(screen.clone());
+    }
+}
+
+impl Drop for Style {
+    // Once the windows using a style are gone, take it off their screens. Otherwise, stylesheets
+    // from previous windows would pile up across config reloads.
+    fn drop(&mut self) {
+        for screen in self.screens.get_mut().iter() {
+            StyleContext::remove_provider_for_screen(screen, &self.builtin);
+            if let Some(ref user) = self.user {
+                StyleContext::remove_provider_for_screen(screen, user);
+            }
+        }
+    }
+}
+
+/// Loads the user's stylesheet, if they have one. If it's invalid, this logs a warning and returns
+/// `None` so that only the built-in style is used.
+fn load_user_style() -> anyhow::Result<Option<CssProvider>> {
+    let style_file = config_dir()?.join("exit-screen.css");
+    if !style_file.exists() {
+        return Ok(None);
+    }
+
+    let provider = CssProvider::new();
+    match provider.load_from_file(&gio::File::new_for_path(&style_file)) {
+        Ok(()) => Ok(Some(provider)),
+        Err(e) => {
+            warn!(
+                "Could not load stylesheet {}, using the built-in style: {}",
+                style_file.display(),
+                e
+            );
+            Ok(None)
+        }
+    }
+}
+
+fn config_dir() -> anyhow::Result<PathBuf> {
+    match ProjectDirs::from("com.bennavetta", "", "desk") {
+        Some(dirs) => Ok(dirs.config_dir().to_path_buf()),
         None => bail!("Home directory does not exist"),
-    };
+    }
+}
 
-    let config_file = dirs.config_dir().join("exit-screen.toml");
+/// Loads the configuration from `config_file`, or from the default location if it's not given. The
+/// default configuration file is optional, but an explicitly-given one must exist.
+fn load_config(config_file: Option<&Path>) -> anyhow::Result<Config> {
+    let explicit = config_file.is_some();
+    let config_file = match config_file {
+        Some(config_file) => config_file.to_path_buf(),
+        None => config_dir()?.join("exit-screen.toml"),
+    };
     match std::fs::read_to_string(&config_file) {
         Ok(contents) => toml::from_str(&contents).with_context(|| {
             format!(
@@ -165,7 +507,7 @@ fn load_config() -> anyhow::Result<Config> {
         }),
         Err(err) => {
             match err.kind() {
-                ErrorKind::NotFound => {
+                ErrorKind::NotFound if !explicit => {
                     debug!("Configuration file {} not found, using defaults", config_file.display());
                     Ok(Config::default())
                 },
@@ -176,16 +518,30 @@ fn load_config() -> anyhow::Result<Config> {
 }
 
 fn run() -> anyhow::Result<()> {
+    let args = Args::from_args();
+
     let app = Application::new(Some("com.bennavetta.desk.exit-screen"), Default::default())
         .context("Could not create GTK application")?;
 
+    // Activating again, such as by launching a second instance, shows the existing windows rather
+    // than building the UI (and its SIGHUP handler) a second time
+    let built = Cell::new(false);
     app.connect_activate(move |app| {
-        if let Err(e) = build_ui(app) {
-            error!("Could not create UI: {}", e);
+        if built.get() {
+            for window in app.get_windows() {
+                window.present();
+            }
+            return;
+        }
+
+        match build_ui(app, args.config.as_deref()) {
+            Ok(()) => built.set(true),
+            Err(e) => error!("Could not create UI: {}", e),
         }
     });
 
-    app.run(&env::args().collect::<Vec<_>>());
+    // GTK would reject our own flags, so it only gets the program name
+    app.run(&env::args().take(1).collect::<Vec<_>>());
 
     Ok(())
 }
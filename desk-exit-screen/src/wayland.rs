@@ -0,0 +1,56 @@
+//! Window placement on Wayland. Compositors don't let clients position their own windows, so the
+//! exit screen uses the layer-shell protocol to cover the whole output instead.
+
+use gdk::Monitor;
+use glib::ObjectExt;
+use gtk::Window;
+#[cfg(feature = "wayland")]
+use gtk_layer_shell::{Edge, Layer};
+#[cfg(not(feature = "wayland"))]
+use log::warn;
+
+/// Turns `window` into a layer-shell overlay when running on Wayland. This must be called before
+/// the window is shown. Returns `false` if not on Wayland, in which case the window should be
+/// placed as a regular X11 window.
+#[cfg(feature = "wayland")]
+pub fn init_layer_shell(window: &Window) -> bool {
+    if !is_wayland() {
+        return false;
+    }
+
+    gtk_layer_shell::init_for_window(window);
+    gtk_layer_shell::set_namespace(window, "desk-exit-screen");
+    gtk_layer_shell::set_layer(window, Layer::Overlay);
+    // Anchoring to every edge stretches the window over the whole output
+    for &edge in &[Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+        gtk_layer_shell::set_anchor(window, edge, true);
+    }
+    // Cover panels and bars too, rather than being placed next to them
+    gtk_layer_shell::set_exclusive_zone(window, -1);
+    gtk_layer_shell::set_keyboard_interactivity(window, true);
+    true
+}
+
+/// Checks if GTK is using its Wayland backend
+fn is_wayland() -> bool {
+    gdk::Display::get_default()
+        .map(|display| display.get_type().name() == "GdkWaylandDisplay")
+        .unwrap_or(false)
+}
+
+#[cfg(not(feature = "wayland"))]
+pub fn init_layer_shell(_window: &Window) -> bool {
+    if is_wayland() {
+        warn!("Built without the wayland feature, so the exit screen can't cover the screen");
+    }
+    false
+}
+
+/// Puts a layer-shell window on the output for `monitor`.
+#[cfg(feature = "wayland")]
+pub fn set_monitor(window: &Window, monitor: &Monitor) {
+    gtk_layer_shell::set_monitor(window, monitor);
+}
+
+#[cfg(not(feature = "wayland"))]
+pub fn set_monitor(_window: &Window, _monitor: &Monitor) {}
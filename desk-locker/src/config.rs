@@ -0,0 +1,100 @@
+//! Configuration file support. Settings are read from `~/.config/desk/locker.toml`, and can be
+//! overridden by command-line flags.
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+
+use anyhow::{bail, Context, Result as AnyResult};
+use directories::ProjectDirs;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Config {
+    /// Screen locker command to run, such as `["xsecurelock"]`.
+    #[serde(default)]
+    pub locker: Vec<String>,
+
+    /// Screen locker commands to try if `locker` can't be started, in order.
+    #[serde(default)]
+    pub fallback_lockers: Vec<Vec<String>>,
+
+    /// Extra environment variables to set for the screen locker, such as `XSECURELOCK_*` settings.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+
+    /// Pass a sleep inhibitor lock file descriptor to the screen locker process.
+    #[serde(default)]
+    pub pass_inhibitor_lock: bool,
+
+    /// Manage the session idle hint.
+    #[serde(default)]
+    pub set_idle_hint: bool,
+
+    /// Lock the screen after this many seconds without user input.
+    #[serde(default)]
+    pub idle_timeout: Option<u64>,
+
+    /// X screen saver timeout to set on startup, in seconds.
+    #[serde(default)]
+    pub screensaver_timeout: Option<u64>,
+
+    /// Lock the screen as soon as the laptop lid is closed.
+    #[serde(default)]
+    pub lock_on_lid: bool,
+
+    /// Turn the screen off with DPMS when locking.
+    #[serde(default)]
+    pub dpms_off: bool,
+
+    /// Seconds to wait before locking when the screen saver or idle timeout triggers.
+    #[serde(default)]
+    pub grace: Option<u64>,
+
+    /// Shell command to run during the grace period.
+    #[serde(default)]
+    pub notify_command: Option<String>,
+
+    /// How often to check for events, in milliseconds.
+    #[serde(default)]
+    pub poll_interval: Option<u64>,
+
+    /// Shell command to run when locking the screen. It runs alongside the screen locker, without
+    /// holding up locking.
+    #[serde(default)]
+    pub pre_lock_command: Option<String>,
+
+    /// Shell command to run after the screen is unlocked.
+    #[serde(default)]
+    pub post_unlock_command: Option<String>,
+}
+
+pub fn load_config() -> AnyResult<Config> {
+    let dirs = match ProjectDirs::from("com.bennavetta", "", "desk") {
+        Some(dirs) => dirs,
+        None => bail!("Home directory does not exist"),
+    };
+
+    let config_file = dirs.config_dir().join("locker.toml");
+    match std::fs::read_to_string(&config_file) {
+        Ok(contents) => toml::from_str(&contents).with_context(|| {
+            format!(
+                "Could not parse configuration file {}",
+                config_file.display()
+            )
+        }),
+        Err(err) => match err.kind() {
+            ErrorKind::NotFound => {
+                debug!(
+                    "Configuration file {} not found, using defaults",
+                    config_file.display()
+                );
+                Ok(Config::default())
+            }
+            _ => Err(err).context(format!(
+                "Could not read configuration file {}",
+                config_file.display()
+            )),
+        },
+    }
+}
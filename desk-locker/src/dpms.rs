@@ -0,0 +1,74 @@
+use std::cell::Cell;
+
+use anyhow::{bail, Context, Result as AnyResult};
+use xcb::dpms;
+
+/// Client for the X11 DPMS extension, used to turn the screen off while it's locked.
+pub struct Dpms {
+    conn: xcb::Connection,
+    /// Whether DPMS was enabled before `set_dpms_off` enabled it, to restore in `set_dpms_on`
+    was_enabled: Cell<Option<bool>>,
+}
+
+impl Dpms {
+    pub fn new() -> AnyResult<Dpms> {
+        let (conn, _) = xcb::Connection::connect(None).context("Could not connect to X server")?;
+
+        let ext_data = conn
+            .get_extension_data(dpms::id())
+            .context("Could not get X DPMS extension data")?;
+        if !ext_data.present() {
+            bail!("X DPMS extension not present");
+        }
+
+        let capable = dpms::capable(&conn)
+            .get_reply()
+            .context("Could not check if the X server is DPMS-capable")?;
+        if !capable.capable() {
+            bail!("X server is not DPMS-capable");
+        }
+
+        Ok(Dpms {
+            conn,
+            was_enabled: Cell::new(None),
+        })
+    }
+
+    /// Turn the screen off. DPMS is enabled if needed, until `set_dpms_on` is called.
+    pub fn set_dpms_off(&self) -> AnyResult<()> {
+        let info = dpms::info(&self.conn)
+            .get_reply()
+            .context("Could not get DPMS state")?;
+        // Only remember the original state, if the screen is turned off more than once
+        if self.was_enabled.get().is_none() {
+            self.was_enabled.set(Some(info.state()));
+        }
+
+        // Forcing the power level only works if DPMS is enabled
+        if !info.state() {
+            dpms::enable_checked(&self.conn)
+                .request_check()
+                .context("Could not enable DPMS")?;
+        }
+        self.force_level(dpms::DPMS_MODE_OFF)
+    }
+
+    /// Turn the screen back on. If DPMS was disabled before `set_dpms_off`, such as with
+    /// `xset -dpms`, it's disabled again.
+    pub fn set_dpms_on(&self) -> AnyResult<()> {
+        self.force_level(dpms::DPMS_MODE_ON)?;
+        if let Some(false) = self.was_enabled.take() {
+            dpms::disable_checked(&self.conn)
+                .request_check()
+                .context("Could not disable DPMS")?;
+        }
+        Ok(())
+    }
+
+    fn force_level(&self, mode: dpms::DpmsMode) -> AnyResult<()> {
+        dpms::force_level_checked(&self.conn, mode as u16)
+            .request_check()
+            .with_context(|| format!("Could not set DPMS power level to {}", mode))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+//! Grace period before locking, so that a lock triggered by inactivity can be cancelled by moving
+//! the mouse or typing. This is similar to `xss-lock`'s notifier.
+
+use std::env;
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+
+pub struct GracePeriod {
+    duration: Duration,
+    notify_command: Option<String>,
+
+    started: Option<Instant>,
+    notifier: Option<Child>,
+}
+
+/// Result of checking on a grace period
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GraceState {
+    /// No grace period is running
+    Inactive,
+    /// The grace period is still running
+    Pending,
+    /// There was user activity during the grace period, so the screen should not be locked
+    Cancelled,
+    /// The grace period ended without user activity, so the screen should be locked
+    Elapsed,
+}
+
+impl GracePeriod {
+    /// Creates a new grace period. If `notify_command` is set, it's run (via the user's shell) when
+    /// the grace period starts and killed when it ends.
+    pub fn new(duration: Duration, notify_command: Option<String>) -> GracePeriod {
+        GracePeriod {
+            duration,
+            notify_command,
+            started: None,
+            notifier: None,
+        }
+    }
+
+    /// Start the grace period, if it's not already running. The notifier is only a courtesy, so
+    /// if it can't be started, the grace period runs without it.
+    pub fn start(&mut self) {
+        if self.started.is_some() {
+            return;
+        }
+
+        debug!("Starting {:?} grace period before locking", self.duration);
+        self.started = Some(Instant::now());
+        if let Some(ref command) = self.notify_command {
+            let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
+            match Command::new(&shell).arg("-c").arg(command).spawn() {
+                Ok(notifier) => self.notifier = Some(notifier),
+                Err(e) => warn!(
+                    "Could not run notify command {} (via {}): {}",
+                    command, shell, e
+                ),
+            }
+        }
+    }
+
+    /// Check on the grace period, given how long the user has been idle.
+    pub fn poll(&mut self, idle_time: Duration) -> GraceState {
+        let elapsed = match self.started {
+            Some(started) => started.elapsed(),
+            None => return GraceState::Inactive,
+        };
+
+        // Without any input, the idle time keeps growing from when the grace period started, so if
+        // it's less than the time since then, there was activity
+        let state = if idle_time < elapsed {
+            GraceState::Cancelled
+        } else if elapsed >= self.duration {
+            GraceState::Elapsed
+        } else {
+            GraceState::Pending
+        };
+
+        if state != GraceState::Pending {
+            self.finish();
+        }
+        state
+    }
+
+    /// End the grace period and stop the notifier, if running.
+    fn finish(&mut self) {
+        self.started = None;
+        if let Some(mut notifier) = self.notifier.take() {
+            // The notifier may well have exited on its own already, so only reaping it can fail
+            let _ = notifier.kill();
+            if let Err(e) = notifier.wait() {
+                warn!("Could not stop notify command: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Built-in idle timeout, for locking the screen without relying on the X screen saver timeout.
+
+use std::time::Duration;
+
+/// Tracks user inactivity against a timeout. The timer fires once each time the user has been idle
+/// for longer than the timeout, and resets as soon as there's activity again.
+pub struct IdleTimer {
+    timeout: Duration,
+    fired: bool,
+}
+
+impl IdleTimer {
+    pub fn new(timeout: Duration) -> IdleTimer {
+        IdleTimer {
+            timeout,
+            fired: false,
+        }
+    }
+
+    /// Update the timer with how long the user has been idle. Returns `true` if the timeout has
+    /// just elapsed.
+    pub fn update(&mut self, idle_time: Duration) -> bool {
+        if idle_time < self.timeout {
+            self.fired = false;
+            false
+        } else if self.fired {
+            false
+        } else {
+            self.fired = true;
+            true
+        }
+    }
+
+    /// Let the timer fire again on the next update if the user is still idle, such as when the
+    /// lock it triggered couldn't happen yet.
+    pub fn rearm(&mut self) {
+        self.fired = false;
+    }
+}
@@ -1,24 +1,101 @@
 //! Core locker implementation.
 
-use std::process::{Child, Command};
+use std::collections::HashMap;
+use std::env;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, Command, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use anyhow::{bail, Context, Result as AnyResult};
-use log::{debug, info};
+use anyhow::{anyhow, bail, Context, Result as AnyResult};
+use log::{debug, error, info, warn};
+use nix::unistd;
 
 use desk_logind::inhibitor::{InhibitEvent, InhibitEventSet, InhibitMode, InhibitorLock};
 use desk_logind::{Logind, SessionId};
 
+use crate::dpms::Dpms;
+
 static INHIBITOR_WHO: &str = "desk-locker";
 static INHIBITOR_WHY: &str = "Lock screen on sleep";
 
+/// A screen locker which exits unsuccessfully before running this long is considered to have
+/// crashed on startup, rather than crashing after locking successfully.
+const STABLE_RUNTIME: Duration = Duration::from_secs(10);
+
+/// Delay before restarting a crashed screen locker the first time. Each rapid failure after that
+/// doubles the delay.
+const INITIAL_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// After this many rapid failures in a row, the screen locker is no longer restarted automatically.
+const MAX_RAPID_FAILURES: u32 = 5;
+
+/// After this many rapid failures in a row, the next screen locker command is tried instead. A
+/// screen locker that only crashes once, such as after running for a while, is restarted as-is.
+const FALLBACK_AFTER_FAILURES: u32 = 2;
+
+/// Shell commands to run around locking and unlocking the screen, such as to pause media.
+#[derive(Default)]
+pub struct Hooks {
+    /// Started once each time the screen is locked, alongside the screen locker. This runs in the
+    /// background, so a slow hook doesn't hold up locking.
+    pub pre_lock: Option<String>,
+    /// Started after the screen is unlocked. This also runs in the background.
+    pub post_unlock: Option<String>,
+}
+
+/// What the screen locker process is doing, as reported by [`Locker::poll_locker`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LockerStatus {
+    /// No screen locker is running, and none is waiting to be restarted
+    NotRunning,
+    /// The screen locker is running, or was just restarted
+    StillRunning,
+    /// The screen locker exited with the given code
+    Exited(i32),
+    /// The screen locker was killed by the given signal
+    Signalled(i32),
+    /// A scheduled restart failed, because none of the screen lockers could be started
+    RestartFailed,
+}
+
+impl LockerStatus {
+    fn from_exit_status(status: ExitStatus) -> LockerStatus {
+        match (status.code(), status.signal()) {
+            (Some(code), _) => LockerStatus::Exited(code),
+            (None, Some(signal)) => LockerStatus::Signalled(signal),
+            // Unix processes always have one or the other
+            (None, None) => unreachable!("Exit status {} has no code or signal", status),
+        }
+    }
+}
+
 pub struct Locker {
     pass_inhibitor_fd: bool,
     manage_idle_hint: bool,
-    locker_command: Vec<String>,
+    /// Screen locker commands to try, in order of preference
+    locker_commands: Vec<Vec<String>>,
+    /// Index of the screen locker command that last started successfully
+    current_command: usize,
+    dpms: Option<Dpms>,
+    hooks: Hooks,
+    /// Extra environment variables for the screen locker
+    env: HashMap<String, String>,
 
     session_id: SessionId,
+    /// Seat the session is on, if this is a multi-seat system. Other seats' sessions are locked
+    /// independently, so only this seat's active session matters.
+    seat: Option<String>,
     inhibitor_lock: Option<InhibitorLock>,
     locker_process: Option<Child>,
+    /// Whether the screen is supposed to be locked. This stays set while a crashed screen locker
+    /// is waiting to be restarted.
+    locked: bool,
+
+    // Crash tracking, to avoid respawning a broken screen locker in a tight loop
+    locker_started: Option<Instant>,
+    rapid_failures: u32,
+    restart_at: Option<Instant>,
 }
 
 /// Screen locker implementation.
@@ -36,27 +113,62 @@ impl Locker {
     ///
     /// # Errors
     /// If unable to determine the session ID or take an inhibitor lock, returns a logind error.
-    /// If the locker command is empty, returns an error message
+    /// If no locker command is provided, returns an error message
+    ///
+    /// The first command in `locker_commands` is preferred. If it can't be started, or keeps
+    /// crashing right after starting, the next one is tried, and so on.
     ///
+    /// If `dpms` is provided, the screen is turned off when locking and back on when unlocking.
+    ///
+    /// The variables in `env` are set for the screen locker process, in addition to this process's
+    /// own environment.
     pub fn new(
         logind: &Logind,
         pass_inhibitor_fd: bool,
         manage_idle_hint: bool,
-        locker_command: Vec<String>,
+        locker_commands: Vec<Vec<String>>,
+        dpms: Option<Dpms>,
+        hooks: Hooks,
+        env: HashMap<String, String>,
     ) -> AnyResult<Locker> {
-        if locker_command.is_empty() {
+        let locker_commands: Vec<Vec<String>> = locker_commands
+            .into_iter()
+            .filter(|command| !command.is_empty())
+            .collect();
+        if locker_commands.is_empty() {
             bail!("Locker command not provided");
         }
 
-        let session_id = desk_logind::session_id()?;
+        let session = logind.current_session()?;
+        let session_id = session.id()?;
+        // With only one seat, there's nothing else that could be using the screen saver
+        let seat = match session.seat()? {
+            Some(seat) if logind.list_seats()?.len() > 1 => {
+                info!(
+                    "Session {} is on {} of a multi-seat system",
+                    session_id, seat
+                );
+                Some(seat)
+            }
+            _ => None,
+        };
         let inhibitor_lock = Locker::take_lock(logind)?;
         Ok(Locker {
             pass_inhibitor_fd,
             manage_idle_hint,
-            locker_command,
+            locker_commands,
+            current_command: 0,
+            dpms,
+            hooks,
+            env,
             session_id,
+            seat,
             inhibitor_lock: Some(inhibitor_lock),
             locker_process: None,
+            locked: false,
+            locker_started: None,
+            rapid_failures: 0,
+            restart_at: None,
         })
     }
 
@@ -87,8 +199,17 @@ impl Locker {
         Ok(())
     }
 
-    /// Starts a new screen locker process, if one isn't already running.
+    /// Starts a new screen locker process, if one isn't already running. If the screen locker
+    /// recently crashed, this waits until it's scheduled to be restarted.
     fn start_locker(&mut self) -> AnyResult<()> {
+        if let Some(restart_at) = self.restart_at {
+            if Instant::now() < restart_at {
+                debug!("Screen locker recently crashed, waiting to restart it");
+                return Ok(());
+            }
+            self.restart_at = None;
+        }
+
         // If there's already a locker, make sure it didn't die
         if let Some(ref mut locker) = self.locker_process {
             // If try_wait returns None, then the locker is still running. However, we have no
@@ -99,18 +220,50 @@ impl Locker {
             }
         }
 
-        debug!("Running screen locker {:?}", self.locker_command);
-        let mut cmd = Command::new(&self.locker_command[0]);
-        self.locker_command.iter().skip(1).for_each(|a| {
-            cmd.arg(a);
-        });
-        if self.pass_inhibitor_fd {
-            let inhibitor = self.inhibitor_lock().dup_fd()?;
-            cmd.env("XSS_SLEEP_LOCK_FD", inhibitor.to_string());
+        let inhibitor = if self.pass_inhibitor_fd {
+            Some(self.inhibitor_lock().dup_fd()?)
+        } else {
+            None
+        };
+
+        // Try the last command that worked first, then fall back to the others in order
+        let candidates =
+            (self.current_command..self.locker_commands.len()).chain(0..self.current_command);
+        let mut spawned = None;
+        for index in candidates {
+            let command = &self.locker_commands[index];
+            debug!("Running screen locker {:?}", command);
+            let mut cmd = Command::new(&command[0]);
+            cmd.args(&command[1..]);
+            cmd.envs(&self.env);
+            if let Some(inhibitor) = inhibitor {
+                cmd.env("XSS_SLEEP_LOCK_FD", inhibitor.to_string());
+            }
+            match cmd.spawn() {
+                Ok(process) => {
+                    spawned = Some((index, process));
+                    break;
+                }
+                Err(e) => warn!("Could not start screen locker {:?}: {}", command, e),
+            }
         }
-        let process = cmd.spawn()?;
+
+        // The screen locker inherited its own copy, and holding on to this one would keep the
+        // inhibitor lock alive after it's released
+        if let Some(inhibitor) = inhibitor {
+            if let Err(e) = unistd::close(inhibitor) {
+                warn!("Could not close inhibitor lock file descriptor: {}", e);
+            }
+        }
+
+        let (index, process) = match spawned {
+            Some(spawned) => spawned,
+            None => bail!("Could not start any screen locker"),
+        };
+        self.current_command = index;
         debug!("Started screen locker with pid {}", process.id());
         self.locker_process = Some(process);
+        self.locker_started = Some(Instant::now());
 
         Ok(())
     }
@@ -144,12 +297,69 @@ impl Locker {
         Ok(())
     }
 
+    /// Update the session's locked hint. Failures are logged rather than returned, since the hint
+    /// is only informational.
+    fn set_locked(&mut self, logind: &Logind, locked: bool) {
+        self.locked = locked;
+        debug!("Setting locked hint to {}", locked);
+        let result = logind
+            .session(&self.session_id)
+            .and_then(|session| session.set_locked_hint(locked));
+        if let Err(e) = result {
+            warn!("Could not set locked hint: {:?}", e);
+        }
+    }
+
+    /// If we're managing DPMS, turn the screen off. Failures are logged rather than returned, since
+    /// the screen is still locked.
+    fn screen_off(&self) {
+        if let Some(ref dpms) = self.dpms {
+            debug!("Turning screen off");
+            if let Err(e) = dpms.set_dpms_off() {
+                warn!("Could not turn screen off: {:?}", e);
+            }
+        }
+    }
+
+    /// If we're managing DPMS, turn the screen back on.
+    fn screen_on(&self) {
+        if let Some(ref dpms) = self.dpms {
+            debug!("Turning screen on");
+            if let Err(e) = dpms.set_dpms_on() {
+                warn!("Could not turn screen on: {:?}", e);
+            }
+        }
+    }
+
+    /// Start the pre-lock hook, if configured. This doesn't wait for it, since locking (and
+    /// releasing the inhibitor lock before sleep) shouldn't wait on something like pausing media.
+    fn pre_lock(&self) {
+        if let Some(ref command) = self.hooks.pre_lock {
+            let command = command.clone();
+            thread::spawn(move || run_hook("pre-lock", &command));
+        }
+    }
+
+    /// Start the post-unlock hook, if configured. Like the pre-lock hook, this doesn't wait for it.
+    fn post_unlock(&self) {
+        if let Some(ref command) = self.hooks.post_unlock {
+            let command = command.clone();
+            thread::spawn(move || run_hook("post-unlock", &command));
+        }
+    }
+
     /// Called when the system is about to sleep. This starts the screen locker if it's not
     /// already running and releases the inhibitor lock.
-    pub fn on_sleep(&mut self) -> AnyResult<()> {
+    pub fn on_sleep(&mut self, logind: &Logind) -> AnyResult<()> {
         info!("Preparing for system sleep");
+        if !self.locked {
+            self.pre_lock();
+        }
         self.start_locker()
             .context("Could not start locker before sleeping")?;
+        if !self.locked {
+            self.set_locked(logind, true);
+        }
         self.release_lock()
             .context("Could not release inhibitor lock, sleep may be delayed")?;
         Ok(())
@@ -162,34 +372,187 @@ impl Locker {
         Ok(())
     }
 
-    /// Lock the screen. This will start the screen locker if it's not already running and, if
-    /// configured with `manage_idle_hint`, set the session's idle hint to `true`.
+    /// Lock the screen. This will start the screen locker if it's not already running, set the
+    /// session's locked hint, and, if configured with `manage_idle_hint`, set the session's idle
+    /// hint to `true`. If configured with DPMS, the screen is also turned off. Does nothing if the
+    /// screen is already locked.
     pub fn lock(&mut self, logind: &Logind) -> AnyResult<()> {
+        if self.locked {
+            debug!("Screen is already locked");
+            return Ok(());
+        }
         info!("Locking screen...");
+        self.pre_lock();
         self.start_locker()?;
+        self.set_locked(logind, true);
         self.set_idle(logind)?;
+        self.screen_off();
         Ok(())
     }
 
-    /// Unlock the screen. This will kill the screen locker if it's running and, if configured with
-    /// `manage_idle_hint`, set the session's idle hint to false.
+    /// Unlock the screen. This will kill the screen locker if it's running, clear the session's
+    /// locked hint, and, if configured with `manage_idle_hint`, set the session's idle hint to
+    /// false. Does nothing if the screen isn't locked.
     pub fn unlock(&mut self, logind: &Logind) -> AnyResult<()> {
+        if !self.locked {
+            debug!("Screen is already unlocked");
+            return Ok(());
+        }
         info!("Unlocking screen...");
+        self.set_locked(logind, false);
+        self.screen_on();
+        self.restart_at = None;
         self.kill_locker()?;
         self.clear_idle(logind)?;
+        // The screen was locked even if the screen locker crashed and is waiting to restart
+        self.post_unlock();
         Ok(())
     }
 
-    /// Called periodically to reap the screen locker process.
-    pub fn poll_locker(&mut self, logind: &Logind) -> AnyResult<()> {
+    /// Checks if our session is in the foreground of its seat. On a multi-seat system, the screen
+    /// saver and idle time should only lock the screen when this is true. On a single-seat system,
+    /// or for a session without a seat, this is always true.
+    pub fn is_foreground(&self, logind: &Logind) -> AnyResult<bool> {
+        let seat = match self.seat {
+            Some(ref seat) => logind.seat(seat)?,
+            None => return Ok(true),
+        };
+        Ok(match seat.active_session()? {
+            Some((id, _)) => id == self.session_id,
+            None => false,
+        })
+    }
+
+    /// Whether the screen is supposed to be locked, including while a crashed screen locker is
+    /// waiting to be restarted.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Clean up before exiting. This kills the screen locker, if running, and releases the
+    /// inhibitor lock so that it doesn't delay sleep or shutdown.
+    pub fn shutdown(&mut self) -> AnyResult<()> {
+        info!("Shutting down");
+        self.restart_at = None;
+        if self.locked {
+            // Also restores the DPMS setting, if locking changed it
+            self.screen_on();
+        }
+        self.kill_locker()?;
+        self.release_lock()
+    }
+
+    /// Called periodically to reap the screen locker process. Returns what the screen locker is
+    /// doing, so the caller can react to it exiting with `on_locker_exited` or `on_locker_crashed`.
+    /// If a restart was scheduled after a crash and is due, the screen locker is restarted.
+    pub fn poll_locker(&mut self) -> AnyResult<LockerStatus> {
         if let Some(ref mut locker) = self.locker_process {
             if let Some(status) = locker.try_wait()? {
                 debug!("Screen locker exited with {}", status);
-                self.clear_idle(logind)?;
                 self.locker_process = None;
+                let runtime = self
+                    .locker_started
+                    .take()
+                    .map(|started| started.elapsed())
+                    .unwrap_or_default();
+                if runtime >= STABLE_RUNTIME {
+                    self.rapid_failures = 0;
+                }
+                return Ok(LockerStatus::from_exit_status(status));
+            } else if let Some(started) = self.locker_started {
+                if started.elapsed() >= STABLE_RUNTIME {
+                    self.rapid_failures = 0;
+                }
             }
+            return Ok(LockerStatus::StillRunning);
         }
 
+        if let Some(restart_at) = self.restart_at {
+            if Instant::now() >= restart_at {
+                info!("Restarting screen locker");
+                if let Err(e) = self.start_locker() {
+                    // The caller treats this like another crash, so it's retried with backoff
+                    warn!("Could not restart screen locker: {:?}", e);
+                    return Ok(LockerStatus::RestartFailed);
+                }
+                return Ok(LockerStatus::StillRunning);
+            }
+        }
+
+        Ok(LockerStatus::NotRunning)
+    }
+
+    /// Called when the screen locker exits cleanly, meaning the user unlocked the screen.
+    pub fn on_locker_exited(&mut self, logind: &Logind) -> AnyResult<()> {
+        self.rapid_failures = 0;
+        if !self.locked {
+            return Ok(());
+        }
+        self.set_locked(logind, false);
+        self.screen_on();
+        self.clear_idle(logind)?;
+        self.post_unlock();
         Ok(())
     }
+
+    /// Called when the screen locker fails, is killed by a signal, or can't be restarted. The
+    /// screen is still supposed to be locked, so the screen locker is restarted with exponential backoff.
+    pub fn on_locker_crashed(&mut self, logind: &Logind) -> AnyResult<()> {
+        self.schedule_restart(logind)
+    }
+
+    /// Schedule restarting the screen locker after it crashed, or give up if it keeps crashing.
+    fn schedule_restart(&mut self, logind: &Logind) -> AnyResult<()> {
+        self.rapid_failures += 1;
+        let crashed = self.current_command;
+        // Don't stick with a screen locker that keeps crashing if there are others to try
+        if self.rapid_failures >= FALLBACK_AFTER_FAILURES {
+            self.current_command = (crashed + 1) % self.locker_commands.len();
+        }
+        if self.rapid_failures >= MAX_RAPID_FAILURES {
+            error!(
+                "Screen locker {:?} failed {} times in a row, not restarting it",
+                self.locker_commands[crashed], self.rapid_failures
+            );
+            // Start over with the preferred screen locker and a fresh backoff the next time the
+            // screen is locked
+            self.rapid_failures = 0;
+            self.current_command = 0;
+            self.set_locked(logind, false);
+            self.screen_on();
+            self.clear_idle(logind)?;
+        } else {
+            let delay = INITIAL_RESTART_DELAY * 2u32.pow(self.rapid_failures - 1);
+            warn!("Screen locker crashed, restarting it in {:?}", delay);
+            self.restart_at = Some(Instant::now() + delay);
+        }
+        Ok(())
+    }
+}
+
+/// Run a hook command through the user's shell. Hooks shouldn't prevent locking or unlocking, so
+/// failures are only logged.
+fn run_hook(name: &str, command: &str) {
+    let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
+    debug!("Running {} hook {}", name, command);
+    let result = Command::new(&shell)
+        .arg("-c")
+        .arg(command)
+        .status()
+        .with_context(|| format!("Could not execute {} (via {})", command, shell))
+        .and_then(|status| {
+            if status.success() {
+                Ok(())
+            } else {
+                Err(anyhow!(
+                    "Command {} (via {}) failed: {}",
+                    command,
+                    shell,
+                    status
+                ))
+            }
+        });
+    if let Err(e) = result {
+        warn!("The {} hook failed: {:?}", name, e);
+    }
 }
@@ -1,20 +1,39 @@
+use std::env;
+use std::iter;
+use std::mem;
 use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::Duration;
 
-use anyhow::Result as AnyResult;
+use anyhow::{anyhow, Result as AnyResult};
 use dbus::blocking::Connection;
 use env_logger::Env;
-use log::{error, info};
+use log::{debug, error, info, warn};
 use structopt::StructOpt;
 
+use desk_logind::inhibitor::InhibitEvent;
 use desk_logind::Logind;
 
-use crate::locker::Locker;
-use crate::screensaver::{ScreenSaver, ScreenSaverEvent};
+use crate::config::Config;
+use crate::dpms::Dpms;
+use crate::grace::{GracePeriod, GraceState};
+use crate::idle::IdleTimer;
+use crate::locker::{Hooks, Locker, LockerStatus};
+use crate::screensaver::{IdleSource, ScreenSaver, ScreenSaverEvent};
+use crate::service::{LockerService, ServiceRequest};
+use crate::wayland::WaylandIdle;
 
+mod config;
+mod dpms;
+mod grace;
+mod idle;
 mod locker;
 mod screensaver;
+mod service;
+mod signals;
+mod wayland;
 
+/// Command-line flags. These override the corresponding settings in `~/.config/desk/locker.toml`.
 #[derive(StructOpt)]
 struct Args {
     /// Pass file descriptor for a sleep inhibitor lock to screen locker process.
@@ -34,82 +53,406 @@ struct Args {
     #[structopt(long, short = "i")]
     set_idle_hint: bool,
 
+    /// Lock the screen after this many seconds without user input.
+    ///
+    /// This uses the X11 screen saver extension's idle counter, so it works independently of the
+    /// X screen saver timeout set with `xset s`.
+    #[structopt(long, value_name = "secs")]
+    idle_timeout: Option<u64>,
+
+    /// Set the X screen saver timeout to this many seconds on startup, like `xset s <secs>`.
+    ///
+    /// The previous timeout is restored when desk-locker exits.
+    #[structopt(long, value_name = "secs")]
+    screensaver_timeout: Option<u64>,
+
+    /// Lock the screen as soon as the laptop lid is closed, without waiting for the system to
+    /// suspend.
+    #[structopt(long)]
+    lock_on_lid: bool,
+
+    /// Turn the screen off with DPMS when locking, and back on when unlocking.
+    #[structopt(long)]
+    dpms_off: bool,
+
+    /// Wait this many seconds before locking when the screen saver or idle timeout triggers. Any
+    /// user input during the grace period cancels locking.
+    #[structopt(long, value_name = "secs")]
+    grace: Option<u64>,
+
+    /// Shell command to run at the start of the grace period, such as a notification. It's killed
+    /// when the grace period ends.
+    #[structopt(long, value_name = "cmd")]
+    notify_command: Option<String>,
+
+    /// How often to check for events, in milliseconds. Larger values use less power but make
+    /// desk-locker slower to react. Defaults to 100ms.
+    #[structopt(long, value_name = "ms")]
+    poll_interval: Option<u64>,
+
+    /// Shell command to run when the screen is locked, such as to pause media. It runs in the
+    /// background, alongside the screen locker.
+    #[structopt(long, value_name = "cmd")]
+    pre_lock_command: Option<String>,
+
+    /// Shell command to run after the screen is unlocked.
+    #[structopt(long, value_name = "cmd")]
+    post_unlock_command: Option<String>,
+
+    /// Screen locker command to try if the main one can't be started, such as `i3lock -n`.
+    /// Arguments are split on whitespace. May be given more than once.
+    #[structopt(long, value_name = "cmd", number_of_values = 1)]
+    fallback_locker: Vec<String>,
+
+    /// Environment variable to set for the screen locker, as `KEY=VALUE`. May be given more than
+    /// once. These are added to any set in the configuration file.
+    #[structopt(
+        long,
+        value_name = "KEY=VALUE",
+        number_of_values = 1,
+        parse(try_from_str = parse_env)
+    )]
+    env: Vec<(String, String)>,
+
+    /// Lock the screen right away, wait for it to be unlocked, and then exit, instead of running in
+    /// the background. Useful for a keybinding.
+    #[structopt(long)]
+    once: bool,
+
     /// Screen locker command to run, such as `xsecurelock` or `i3lock`. This command should not
-    /// fork.
-    #[structopt(required = true)]
+    /// fork. Required unless set in the configuration file.
     locker: Vec<String>,
 }
 
+impl Args {
+    /// Apply command-line overrides on top of the configuration file.
+    fn apply(self, mut config: Config) -> Config {
+        if self.pass_inhibitor_lock {
+            config.pass_inhibitor_lock = true;
+        }
+        if self.set_idle_hint {
+            config.set_idle_hint = true;
+        }
+        if self.lock_on_lid {
+            config.lock_on_lid = true;
+        }
+        if self.dpms_off {
+            config.dpms_off = true;
+        }
+        if self.idle_timeout.is_some() {
+            config.idle_timeout = self.idle_timeout;
+        }
+        if self.screensaver_timeout.is_some() {
+            config.screensaver_timeout = self.screensaver_timeout;
+        }
+        if self.grace.is_some() {
+            config.grace = self.grace;
+        }
+        if self.notify_command.is_some() {
+            config.notify_command = self.notify_command;
+        }
+        if self.poll_interval.is_some() {
+            config.poll_interval = self.poll_interval;
+        }
+        if self.pre_lock_command.is_some() {
+            config.pre_lock_command = self.pre_lock_command;
+        }
+        if self.post_unlock_command.is_some() {
+            config.post_unlock_command = self.post_unlock_command;
+        }
+        if !self.fallback_locker.is_empty() {
+            config.fallback_lockers = self
+                .fallback_locker
+                .iter()
+                .map(|command| command.split_whitespace().map(String::from).collect())
+                .collect();
+        }
+        if !self.locker.is_empty() {
+            config.locker = self.locker;
+        }
+        config.env.extend(self.env);
+        config
+    }
+}
+
+/// Parses a `KEY=VALUE` environment variable assignment.
+fn parse_env(assignment: &str) -> Result<(String, String), String> {
+    match assignment.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("Expected KEY=VALUE, got `{}`", assignment)),
+    }
+}
+
 fn run(args: Args) -> AnyResult<()> {
-    let screen_saver = ScreenSaver::new()?;
+    let once = args.once;
+    let config = args.apply(config::load_config()?);
+    let poll_interval = Duration::from_millis(config.poll_interval.unwrap_or(100));
 
-    let conn = Connection::new_system()?;
+    signals::install_handlers()?;
+
+    let dpms = if config.dpms_off {
+        match Dpms::new() {
+            Ok(dpms) => Some(dpms),
+            Err(e) => {
+                warn!(
+                    "DPMS is unavailable, screen will not be turned off: {:?}",
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
 
+    let conn = Connection::new_system()?;
     let logind = Logind::new(&conn);
-    let locker = Arc::new(Mutex::new(Locker::new(
+    let mut locker = Locker::new(
         &logind,
-        args.pass_inhibitor_lock,
-        args.set_idle_hint,
-        args.locker,
-    )?));
+        config.pass_inhibitor_lock,
+        config.set_idle_hint,
+        iter::once(config.locker)
+            .chain(config.fallback_lockers)
+            .collect(),
+        dpms,
+        Hooks {
+            pre_lock: config.pre_lock_command,
+            post_unlock: config.post_unlock_command,
+        },
+        config.env,
+    )?;
+
+    if once {
+        return run_once(&mut locker, &logind, poll_interval);
+    }
+    let locker = Arc::new(Mutex::new(locker));
+
+    // The Wayland protocol only notifies after a fixed timeout, so it takes the place of the idle
+    // timer rather than the X screen saver's own timeout
+    let wayland = env::var_os("WAYLAND_DISPLAY").is_some();
+    let mut idle_source: Box<dyn IdleSource> = if wayland {
+        let timeout = config
+            .idle_timeout
+            .ok_or_else(|| anyhow!("An idle timeout must be set on Wayland"))?;
+        if config.screensaver_timeout.is_some() {
+            warn!("There is no screen saver timeout on Wayland, ignoring it");
+        }
+        Box::new(WaylandIdle::new(Duration::from_secs(timeout))?)
+    } else {
+        let mut screen_saver = ScreenSaver::new()?;
+        if let Some(secs) = config.screensaver_timeout {
+            screen_saver.set_timeout(Duration::from_secs(secs))?;
+        }
+        Box::new(screen_saver)
+    };
+
+    // The D-Bus service is a convenience, so desk-locker still works without it
+    let mut service = match LockerService::new() {
+        Ok(service) => Some(service),
+        Err(e) => {
+            warn!("Could not start D-Bus service: {:?}", e);
+            None
+        }
+    };
 
     // Set up session lock/unlock callbacks
     let session = logind.current_session()?;
 
-    {
+    let lock_token = {
         let locker = locker.clone();
-        session.on_lock(move |logind| {
-            if let Err(e) = locker.lock().unwrap().lock(&logind) {
-                error!("Handling lock failed: {:?}", e);
-            }
-        })?;
-    }
+        session.on_lock(move |logind| Ok(locker.lock().unwrap().lock(&logind)?))?
+    };
 
-    {
+    let unlock_token = {
         let locker = locker.clone();
-        session.on_unlock(move |logind| {
-            if let Err(e) = locker.lock().unwrap().unlock(&logind) {
-                error!("Handling unlock failed: {:?}", e);
-            }
-        })?;
-    }
+        session.on_unlock(move |logind| Ok(locker.lock().unwrap().unlock(&logind)?))?
+    };
 
     // Then set up sleep/resume callbacks
     let sleep_locker = locker.clone();
     let resume_locker = locker.clone();
-    logind.on_sleep(
-        move |_logind| {
-            if let Err(e) = sleep_locker.lock().unwrap().on_sleep() {
-                error!("Handling sleep failed: {:?}", e);
-            }
-        },
-        move |logind| {
-            if let Err(e) = resume_locker.lock().unwrap().on_resume(&logind) {
-                error!("Handling resume failed: {:?}", e);
-            }
-        },
+    let sleep_token = logind.on_sleep(
+        move |logind| Ok(sleep_locker.lock().unwrap().on_sleep(&logind)?),
+        move |logind| Ok(resume_locker.lock().unwrap().on_resume(&logind)?),
     )?;
 
+    // logind doesn't signal changes to the lid state, so it's polled. This starts out unknown, so
+    // that starting up with the lid already closed (like when docked) doesn't lock.
+    let lock_on_lid = config.lock_on_lid;
+    let mut lid_was_closed = None;
+
+    let idle_timeout = config.idle_timeout.map(Duration::from_secs);
+    let mut idle_timer = idle_timeout.filter(|_| !wayland).map(IdleTimer::new);
+    let notify_command = config.notify_command;
+    let mut grace = config
+        .grace
+        .map(|secs| GracePeriod::new(Duration::from_secs(secs), notify_command));
+
+    // If the screen saver came on before we started, there won't be an event for it
+    let mut already_on = match idle_source.query_state() {
+        Ok(ScreenSaverEvent::On) | Ok(ScreenSaverEvent::Cycle) => {
+            info!("Screen saver is already on");
+            true
+        }
+        Ok(_) => false,
+        Err(e) => {
+            warn!("Could not check screen saver state: {:?}", e);
+            false
+        }
+    };
+
     info!("Waiting for events...");
-    loop {
-        if let Err(e) = conn.process(Duration::from_millis(100)) {
-            error!("Processing D-Bus events failed: {:?}", e);
+    let event_loop = logind
+        .event_loop()
+        .tick(poll_interval)
+        .subscribe(lock_token)
+        .subscribe(unlock_token)
+        .subscribe(sleep_token);
+    let stop = event_loop.stop_handle();
+    event_loop.run_with(|| -> AnyResult<()> {
+        if signals::terminated() {
+            stop.stop();
+            return Ok(());
         }
 
-        // Must not hold lock while calling conn.process - since the logind signal callbacks also
-        // use the locker, this can deadlock
+        // The event loop doesn't hold the lock while processing D-Bus messages - since the
+        // logind signal callbacks also use the locker, that would deadlock
         let mut locker = locker.lock().unwrap();
-        locker.poll_locker(&Logind::new(&conn))?;
-
-        if let Some(event) = screen_saver.poll_event() {
-            let logind = Logind::new(&conn);
-            match event {
-                ScreenSaverEvent::On | ScreenSaverEvent::Cycle => locker.lock(&logind)?,
-                // Do not unlock when the screen saver deactivates - that defeats the point of having this :P
-                _ => (),
+        match locker.poll_locker()? {
+            LockerStatus::Exited(0) => locker.on_locker_exited(&logind)?,
+            LockerStatus::Exited(_) | LockerStatus::Signalled(_) | LockerStatus::RestartFailed => {
+                locker.on_locker_crashed(&logind)?
+            }
+            LockerStatus::NotRunning | LockerStatus::StillRunning => (),
+        }
+
+        if lock_on_lid {
+            match logind.lid_closed() {
+                Ok(closed) => {
+                    if closed && lid_was_closed == Some(false) {
+                        info!("Lid closed");
+                        locker.lock(&logind)?;
+                    }
+                    lid_was_closed = Some(closed);
+                }
+                Err(e) => warn!("Could not check if the lid is closed: {:?}", e),
+            }
+        }
+
+        if let Some(ref active_service) = service {
+            // Failed requests are reported back to the caller, so only a broken session bus
+            // connection ends up here
+            let result = active_service.poll(|request| match request {
+                ServiceRequest::Lock => locker.lock(&logind),
+                ServiceRequest::Unlock => locker.unlock(&logind),
+            });
+            if let Err(e) = result {
+                warn!("D-Bus service failed, stopping it: {:?}", e);
+                service = None;
+            }
+        }
+
+        // Do not unlock when the screen saver deactivates - that defeats the point of having this :P
+        let event = idle_source.poll_event()?;
+        let screen_saver_on = matches!(
+            event,
+            Some(ScreenSaverEvent::On) | Some(ScreenSaverEvent::Cycle)
+        );
+        // On Wayland, the compositor's notification is the idle timeout rather than a screen saver
+        let mut idle_triggered = wayland && screen_saver_on;
+        let mut lock_triggered = mem::take(&mut already_on) || (!wayland && screen_saver_on);
+
+        if let Some(ref mut idle_timer) = idle_timer {
+            if idle_timer.update(idle_source.idle_time()?) {
+                idle_triggered = true;
+            }
+        }
+
+        if idle_triggered {
+            // Respect idle inhibitors, such as from video players. Unlike the screen saver, the
+            // idle timeout is entirely up to us.
+            match logind.block_inhibited() {
+                Ok(blocked) if blocked.contains(InhibitEvent::Idle) => {
+                    debug!("Idle is inhibited, not locking");
+                    // Start counting again, so the timeout doesn't fire on every tick until the
+                    // inhibitor goes away. The compositor only notifies once per idle period, so
+                    // there's nothing to do on Wayland.
+                    if let Some(ref mut idle_timer) = idle_timer {
+                        if let Err(e) = idle_source.reset_idle_timer() {
+                            warn!("Could not reset idle time: {:?}", e);
+                        }
+                        idle_timer.rearm();
+                    }
+                }
+                Ok(_) => {
+                    if let Some(timeout) = idle_timeout {
+                        info!("Idle for {:?}", timeout);
+                    }
+                    lock_triggered = true;
+                }
+                Err(e) => {
+                    // Try again on the next tick, rather than stopping desk-locker
+                    warn!("Could not check idle inhibitors: {:?}", e);
+                    if let Some(ref mut idle_timer) = idle_timer {
+                        idle_timer.rearm();
+                    }
+                }
+            }
+        }
+
+        if lock_triggered {
+            match locker.is_foreground(&logind) {
+                Ok(true) => (),
+                Ok(false) => {
+                    debug!("Session isn't active on its seat, not locking");
+                    lock_triggered = false;
+                }
+                // The screen saver only says it came on once, so skipping this could leave the
+                // screen unlocked for good. Locking is the safer mistake.
+                Err(e) => warn!(
+                    "Could not check if session is active, locking anyway: {:?}",
+                    e
+                ),
+            }
+        }
+
+        match grace {
+            Some(ref mut grace) => {
+                if lock_triggered {
+                    grace.start();
+                }
+                match grace.poll(idle_source.idle_time()?) {
+                    GraceState::Elapsed => locker.lock(&logind)?,
+                    GraceState::Cancelled => info!("Activity during grace period, not locking"),
+                    GraceState::Pending | GraceState::Inactive => (),
+                }
+            }
+            None if lock_triggered => locker.lock(&logind)?,
+            None => (),
+        }
+        Ok(())
+    })?;
+
+    let mut locker = locker.lock().unwrap();
+    locker.shutdown()
+}
+
+/// Lock the screen and wait until it's unlocked, for `--once`. There are no logind signals to
+/// react to, so this only watches the screen locker process.
+fn run_once(locker: &mut Locker, logind: &Logind, poll_interval: Duration) -> AnyResult<()> {
+    locker.lock(logind)?;
+    // Crashes are handled as usual, so the screen stays locked until the screen locker exits
+    // cleanly or gives up
+    while locker.is_locked() && !signals::terminated() {
+        match locker.poll_locker()? {
+            LockerStatus::Exited(0) => locker.on_locker_exited(logind)?,
+            LockerStatus::Exited(_) | LockerStatus::Signalled(_) | LockerStatus::RestartFailed => {
+                locker.on_locker_crashed(logind)?
             }
+            LockerStatus::NotRunning | LockerStatus::StillRunning => thread::sleep(poll_interval),
         }
     }
+    locker.shutdown()
 }
 
 pub fn main() {
@@ -1,11 +1,47 @@
+use std::time::Duration;
+
 use anyhow::{anyhow, bail, Context, Result as AnyResult};
-use xcb::screensaver;
+use log::{debug, warn};
+use xcb::{randr, screensaver};
+
+/// Something that can tell when the user is idle. This is the X11 screen saver extension, or the
+/// compositor on Wayland.
+pub trait IdleSource {
+    /// The current state, for catching up on events from before desk-locker started.
+    fn query_state(&self) -> AnyResult<ScreenSaverEvent>;
+
+    /// Check for a state change, without blocking.
+    fn poll_event(&mut self) -> AnyResult<Option<ScreenSaverEvent>>;
+
+    /// How long it's been since the last user input.
+    fn idle_time(&self) -> AnyResult<Duration>;
+
+    /// Act as though there was user input, restarting the idle time. This does nothing if the
+    /// source has no way to do that.
+    fn reset_idle_timer(&self) -> AnyResult<()> {
+        Ok(())
+    }
+}
 
 /// Client for the [X11 screen saver extension](https://www.x.org/releases/X11R7.7/doc/scrnsaverproto/saver.html).
-/// For now, only supports listening for screen saver events.
+/// Supports listening for screen saver events and querying how long the user has been idle.
 pub struct ScreenSaver {
     conn: xcb::Connection,
+    root: xcb::Window,
     notify_event: u8,
+    /// X11 event response type for RandR screen changes, if RandR is available
+    screen_change_event: Option<u8>,
+    /// Screen saver settings from before `set_timeout`, to restore on exit
+    saved_settings: Option<Settings>,
+}
+
+/// Screen saver settings, as set with `xset s`
+#[derive(Debug, Copy, Clone)]
+struct Settings {
+    timeout: i16,
+    interval: i16,
+    prefer_blanking: u8,
+    allow_exposures: u8,
 }
 
 impl ScreenSaver {
@@ -23,30 +59,117 @@ impl ScreenSaver {
         // Figure out the actual X11 event response type we'll see
         let notify_event = ext_data.first_event() + screensaver::NOTIFY;
 
-        // TODO: check extension protocol version
+        // Version 1.1 added the screen saver state to QueryInfo and cycle events
+        let version = screensaver::query_version(
+            &conn,
+            screensaver::MAJOR_VERSION as u8,
+            screensaver::MINOR_VERSION as u8,
+        )
+        .get_reply()
+        .context("Could not query X Screen Saver extension version")?;
+        let found = (
+            u32::from(version.server_major_version()),
+            u32::from(version.server_minor_version()),
+        );
+        let required = (screensaver::MAJOR_VERSION, screensaver::MINOR_VERSION);
+        if found < required {
+            bail!(
+                "X Screen Saver extension version {}.{} is too old, {}.{} or newer is required",
+                found.0,
+                found.1,
+                required.0,
+                required.1
+            );
+        }
 
-        // TODO: multi-monitor support (can watch for screens being added/removed)
         let setup = conn.get_setup();
         let screen = setup
             .roots()
             .nth(screen_num as usize)
             .ok_or_else(|| anyhow!("Could not get X11 screen {}", screen_num))?;
+        let root = screen.root();
 
-        screensaver::select_input_checked(
-            &conn,
-            screen.root(),
-            screensaver::EVENT_NOTIFY_MASK | screensaver::EVENT_CYCLE_MASK,
+        select_screen_saver_input(&conn)?;
+        let screen_change_event = watch_screen_changes(&conn);
+
+        Ok(ScreenSaver {
+            conn,
+            root,
+            notify_event,
+            screen_change_event,
+            saved_settings: None,
+        })
+    }
+
+    /// How long it's been since the last user input, according to the X server.
+    pub fn idle_time(&self) -> AnyResult<Duration> {
+        let info = screensaver::query_info(&self.conn, self.root)
+            .get_reply()
+            .context("Could not query X11 screen saver info")?;
+        Ok(Duration::from_millis(info.ms_since_user_input().into()))
+    }
+
+    /// Reset the X server's idle time, as if there was user input. This applies to the whole X
+    /// server, not just desk-locker, so it also postpones screen blanking and DPMS.
+    pub fn reset_idle_timer(&self) -> AnyResult<()> {
+        xcb::force_screen_saver_checked(&self.conn, xcb::SCREEN_SAVER_RESET as u8)
+            .request_check()
+            .context("Could not reset X11 screen saver")?;
+        Ok(())
+    }
+
+    /// Set how long the X server waits without user input before turning the screen saver on. This
+    /// affects the whole X server. The previous settings are restored when the `ScreenSaver` is
+    /// dropped.
+    pub fn set_timeout(&mut self, timeout: Duration) -> AnyResult<()> {
+        let current = xcb::get_screen_saver(&self.conn)
+            .get_reply()
+            .context("Could not get X11 screen saver settings")?;
+        let current = Settings {
+            timeout: current.timeout() as i16,
+            interval: current.interval() as i16,
+            prefer_blanking: current.prefer_blanking(),
+            allow_exposures: current.allow_exposures(),
+        };
+
+        let secs = timeout.as_secs();
+        if secs > i16::MAX as u64 {
+            bail!("Screen saver timeout of {}s is too long", secs);
+        }
+        self.apply_settings(Settings {
+            timeout: secs as i16,
+            ..current
+        })?;
+
+        // Only remember the original settings, if this is called more than once
+        self.saved_settings.get_or_insert(current);
+        Ok(())
+    }
+
+    fn apply_settings(&self, settings: Settings) -> AnyResult<()> {
+        xcb::set_screen_saver_checked(
+            &self.conn,
+            settings.timeout,
+            settings.interval,
+            settings.prefer_blanking,
+            settings.allow_exposures,
         )
         .request_check()
-        .context(anyhow!(
-            "Could not subscribe to X11 screen saver events on screen {}",
-            screen_num
-        ))?;
+        .context("Could not set X11 screen saver settings")?;
+        Ok(())
+    }
 
-        Ok(ScreenSaver { conn, notify_event })
+    /// The current screen saver state, for catching up on events from before desk-locker started.
+    pub fn query_state(&self) -> AnyResult<ScreenSaverEvent> {
+        let info = screensaver::query_info(&self.conn, self.root)
+            .get_reply()
+            .context("Could not query X11 screen saver info")?;
+        ScreenSaverEvent::from_state(info.state())
+            .ok_or_else(|| anyhow!("Unknown X11 screen saver state {}", info.state()))
     }
 
-    pub fn poll_event(&self) -> Option<ScreenSaverEvent> {
+    /// Check for a screen saver event, without blocking.
+    pub fn poll_event(&self) -> Option<ScreenSaverNotify> {
         self.conn.poll_for_event().and_then(|event| {
             // Don't know why this is needed, but _every_ XCB example I've seen does it
             let event_type = event.response_type() & !0x80;
@@ -54,13 +177,21 @@ impl ScreenSaver {
                 // Safety: verified above that this is a NotifyEvent, according to the event type from the extension data
                 let event: &xcb::screensaver::NotifyEvent = unsafe { xcb::cast_event(&event) };
 
-                match event.state() as u32 {
-                    screensaver::STATE_OFF => Some(ScreenSaverEvent::Off),
-                    screensaver::STATE_ON => Some(ScreenSaverEvent::On),
-                    screensaver::STATE_CYCLE => Some(ScreenSaverEvent::Cycle),
-                    screensaver::STATE_DISABLED => Some(ScreenSaverEvent::Disabled),
-                    _ => None,
+                Some(ScreenSaverNotify {
+                    event: ScreenSaverEvent::from_state(event.state())?,
+                    time: event.time(),
+                    kind: ScreenSaverKind::from_kind(event.kind()),
+                    window: event.window(),
+                    forced: event.forced(),
+                })
+            } else if Some(event_type) == self.screen_change_event {
+                // Monitors were added or removed, so make sure we're still watching every screen
+                debug!("X11 screen configuration changed");
+                if let Err(e) = select_screen_saver_input(&self.conn) {
+                    warn!("Could not resubscribe to screen saver events: {:?}", e);
                 }
+                watch_screen_changes(&self.conn);
+                None
             } else {
                 None
             }
@@ -68,6 +199,89 @@ impl ScreenSaver {
     }
 }
 
+/// Subscribe to screen saver events on the root window of every screen.
+fn select_screen_saver_input(conn: &xcb::Connection) -> AnyResult<()> {
+    for (screen_num, screen) in conn.get_setup().roots().enumerate() {
+        screensaver::select_input_checked(
+            conn,
+            screen.root(),
+            screensaver::EVENT_NOTIFY_MASK | screensaver::EVENT_CYCLE_MASK,
+        )
+        .request_check()
+        .context(anyhow!(
+            "Could not subscribe to X11 screen saver events on screen {}",
+            screen_num
+        ))?;
+    }
+    Ok(())
+}
+
+/// Subscribe to RandR screen change events on the root window of every screen, returning the
+/// event response type to look for. This is optional, so failures are only logged.
+fn watch_screen_changes(conn: &xcb::Connection) -> Option<u8> {
+    let ext_data = conn.get_extension_data(randr::id())?;
+    if !ext_data.present() {
+        debug!("X RandR extension not present, not watching for monitor changes");
+        return None;
+    }
+
+    let mask = randr::NOTIFY_MASK_SCREEN_CHANGE as u16;
+    let mut watching = false;
+    for (screen_num, screen) in conn.get_setup().roots().enumerate() {
+        match randr::select_input_checked(conn, screen.root(), mask).request_check() {
+            Ok(()) => watching = true,
+            Err(e) => warn!(
+                "Could not watch for X11 monitor changes on screen {}: {:?}",
+                screen_num, e
+            ),
+        }
+    }
+
+    if watching {
+        Some(ext_data.first_event() + randr::SCREEN_CHANGE_NOTIFY)
+    } else {
+        None
+    }
+}
+
+impl Drop for ScreenSaver {
+    fn drop(&mut self) {
+        if let Some(settings) = self.saved_settings.take() {
+            if let Err(e) = self.apply_settings(settings) {
+                warn!("Could not restore screen saver settings: {:?}", e);
+            }
+        }
+    }
+}
+
+impl IdleSource for ScreenSaver {
+    fn query_state(&self) -> AnyResult<ScreenSaverEvent> {
+        ScreenSaver::query_state(self)
+    }
+
+    fn poll_event(&mut self) -> AnyResult<Option<ScreenSaverEvent>> {
+        Ok(ScreenSaver::poll_event(self).map(|notify| {
+            debug!(
+                "Screen saver {:?} at {}ms ({:?}, window {}{})",
+                notify.event,
+                notify.time,
+                notify.kind,
+                notify.window,
+                if notify.forced { ", forced" } else { "" }
+            );
+            notify.event
+        }))
+    }
+
+    fn idle_time(&self) -> AnyResult<Duration> {
+        ScreenSaver::idle_time(self)
+    }
+
+    fn reset_idle_timer(&self) -> AnyResult<()> {
+        ScreenSaver::reset_idle_timer(self)
+    }
+}
+
 /// Events produced by X11 on screen saver state changes.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ScreenSaverEvent {
@@ -83,3 +297,63 @@ pub enum ScreenSaverEvent {
     /// The screen saver was disabled
     Disabled,
 }
+
+/// A screen saver event, along with the details X11 sends with it. These are mostly useful for
+/// figuring out why the screen locked.
+#[derive(Debug, Copy, Clone)]
+pub struct ScreenSaverNotify {
+    /// What happened to the screen saver
+    pub event: ScreenSaverEvent,
+
+    /// X server time of the event, in milliseconds
+    pub time: xcb::Timestamp,
+
+    /// How the screen saver is being shown
+    pub kind: ScreenSaverKind,
+
+    /// The screen saver window
+    pub window: xcb::Window,
+
+    /// Whether the screen saver was turned on or off by a client, such as with `xset s activate`,
+    /// rather than by the idle timeout or user input
+    pub forced: bool,
+}
+
+/// How the X server is showing the screen saver.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ScreenSaverKind {
+    /// The screen was blanked
+    Blanked,
+
+    /// The X server drew its own screen saver
+    Internal,
+
+    /// A client drew the screen saver in the screen saver window
+    External,
+
+    /// A kind this doesn't know about. This is only informational, so the event is still used.
+    Other(u8),
+}
+
+impl ScreenSaverKind {
+    fn from_kind(kind: u8) -> ScreenSaverKind {
+        match kind as u32 {
+            screensaver::KIND_BLANKED => ScreenSaverKind::Blanked,
+            screensaver::KIND_INTERNAL => ScreenSaverKind::Internal,
+            screensaver::KIND_EXTERNAL => ScreenSaverKind::External,
+            _ => ScreenSaverKind::Other(kind),
+        }
+    }
+}
+
+impl ScreenSaverEvent {
+    fn from_state(state: u8) -> Option<ScreenSaverEvent> {
+        match state as u32 {
+            screensaver::STATE_OFF => Some(ScreenSaverEvent::Off),
+            screensaver::STATE_ON => Some(ScreenSaverEvent::On),
+            screensaver::STATE_CYCLE => Some(ScreenSaverEvent::Cycle),
+            screensaver::STATE_DISABLED => Some(ScreenSaverEvent::Disabled),
+            _ => None,
+        }
+    }
+}
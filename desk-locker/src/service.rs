@@ -0,0 +1,112 @@
+//! D-Bus service on the session bus, so that other tools can ask the running desk-locker to lock
+//! or unlock the screen. For example:
+//!
+//! ```shell script
+//! $ dbus-send --session --type=method_call --dest=com.bennavetta.desk.Locker \
+//!     /com/bennavetta/desk/Locker com.bennavetta.desk.Locker.Lock
+//! ```
+
+use std::ffi::CString;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result as AnyResult};
+use dbus::blocking::stdintf::org_freedesktop_dbus::RequestNameReply;
+use dbus::blocking::Connection;
+use dbus::channel::{MatchingReceiver, Sender};
+use dbus::message::MatchRule;
+use dbus::strings::ErrorName;
+use dbus::Message;
+use log::{debug, warn};
+
+static SERVICE_NAME: &str = "com.bennavetta.desk.Locker";
+static OBJECT_PATH: &str = "/com/bennavetta/desk/Locker";
+static INTERFACE: &str = "com.bennavetta.desk.Locker";
+static FAILED_ERROR: &str = "com.bennavetta.desk.Locker.Error.Failed";
+static UNKNOWN_METHOD_ERROR: &str = "org.freedesktop.DBus.Error.UnknownMethod";
+
+/// Requests made through the D-Bus service
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ServiceRequest {
+    Lock,
+    Unlock,
+}
+
+pub struct LockerService {
+    conn: Connection,
+    calls: Receiver<Message>,
+}
+
+impl LockerService {
+    /// Connects to the session bus and claims the service name. Fails if another desk-locker
+    /// already owns it.
+    pub fn new() -> AnyResult<LockerService> {
+        let conn = Connection::new_session().context("Could not connect to session bus")?;
+        let reply = conn
+            .request_name(SERVICE_NAME, false, false, true)
+            .with_context(|| format!("Could not request D-Bus name {}", SERVICE_NAME))?;
+        if reply != RequestNameReply::PrimaryOwner {
+            bail!("D-Bus name {} is already taken", SERVICE_NAME);
+        }
+
+        // Calls are queued up rather than handled directly, since handling them needs the locker
+        // and system bus connection owned by the main loop
+        let (sender, calls) = mpsc::channel();
+        let mut rule = MatchRule::new_method_call();
+        rule.path = Some(OBJECT_PATH.into());
+        rule.interface = Some(INTERFACE.into());
+        conn.start_receive(
+            rule,
+            Box::new(move |msg, _| {
+                let _ = sender.send(msg);
+                true
+            }),
+        );
+
+        Ok(LockerService { conn, calls })
+    }
+
+    /// Process pending D-Bus messages without blocking, calling `handler` for each request. The
+    /// caller gets an error reply if `handler` fails, so this only returns an error if the session
+    /// bus connection itself fails.
+    pub fn poll<F: FnMut(ServiceRequest) -> AnyResult<()>>(&self, mut handler: F) -> AnyResult<()> {
+        self.conn
+            .process(Duration::from_millis(0))
+            .context("Processing session bus messages failed")?;
+
+        for call in self.calls.try_iter() {
+            let request = match call.member().as_deref() {
+                Some("Lock") => ServiceRequest::Lock,
+                Some("Unlock") => ServiceRequest::Unlock,
+                other => {
+                    let message = format!("Unknown method {:?}", other);
+                    self.reply(error_reply(&call, UNKNOWN_METHOD_ERROR, message));
+                    continue;
+                }
+            };
+
+            debug!("Received {:?} request over D-Bus", request);
+            let reply = match handler(request) {
+                Ok(()) => call.method_return(),
+                Err(e) => {
+                    warn!("{:?} request over D-Bus failed: {:?}", request, e);
+                    error_reply(&call, FAILED_ERROR, format!("{:#}", e))
+                }
+            };
+            self.reply(reply);
+        }
+
+        Ok(())
+    }
+
+    fn reply(&self, reply: Message) {
+        if self.conn.send(reply).is_err() {
+            warn!("Could not send D-Bus reply");
+        }
+    }
+}
+
+fn error_reply(call: &Message, name: &'static str, message: String) -> Message {
+    let message = CString::new(message).unwrap_or_default();
+    call.error(&ErrorName::from(name), &message)
+}
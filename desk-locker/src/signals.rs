@@ -0,0 +1,35 @@
+//! Handling for termination signals, so that desk-locker can release its inhibitor lock and stop
+//! the screen locker before exiting.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{Context, Result as AnyResult};
+use nix::libc;
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+
+static TERMINATED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_termination(_: libc::c_int) {
+    // Only async-signal-safe operations are allowed here, so just set a flag for the main loop
+    TERMINATED.store(true, Ordering::SeqCst);
+}
+
+/// Install handlers for `SIGTERM` and `SIGINT`.
+pub fn install_handlers() -> AnyResult<()> {
+    let action = SigAction::new(
+        SigHandler::Handler(handle_termination),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    for &signal in [Signal::SIGTERM, Signal::SIGINT].iter() {
+        // Safety: the handler only touches an atomic
+        unsafe { sigaction(signal, &action) }
+            .with_context(|| format!("Could not install {} handler", signal))?;
+    }
+    Ok(())
+}
+
+/// Checks if a termination signal has been received.
+pub fn terminated() -> bool {
+    TERMINATED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,439 @@
+//! Idle detection on Wayland, using the [`ext-idle-notify-v1`](https://wayland.app/protocols/ext-idle-notify-v1)
+//! protocol supported by sway and other wlroots-based compositors.
+//!
+//! Only a handful of messages are needed, so this speaks the Wayland wire protocol directly rather
+//! than pulling in a full client library.
+
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::env;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result as AnyResult};
+
+use crate::screensaver::{IdleSource, ScreenSaverEvent};
+
+const DISPLAY_ID: u32 = 1;
+const REGISTRY_ID: u32 = 2;
+const SYNC_CALLBACK_ID: u32 = 3;
+const NOTIFIER_ID: u32 = 4;
+const SEAT_ID: u32 = 5;
+const NOTIFICATION_ID: u32 = 6;
+
+// Opcodes for the requests and events used, by interface
+const DISPLAY_SYNC: u16 = 0;
+const DISPLAY_GET_REGISTRY: u16 = 1;
+const DISPLAY_ERROR: u16 = 0;
+const REGISTRY_BIND: u16 = 0;
+const REGISTRY_GLOBAL: u16 = 0;
+const CALLBACK_DONE: u16 = 0;
+const NOTIFIER_GET_IDLE_NOTIFICATION: u16 = 1;
+const NOTIFICATION_IDLED: u16 = 0;
+const NOTIFICATION_RESUMED: u16 = 1;
+
+const NOTIFIER_INTERFACE: &str = "ext_idle_notifier_v1";
+const SEAT_INTERFACE: &str = "wl_seat";
+
+/// Idle source backed by a Wayland compositor. The compositor tells us when the user has been idle
+/// for the configured timeout and when they come back, which are reported as the screen saver
+/// turning on and off.
+pub struct WaylandIdle {
+    socket: UnixStream,
+    buffer: Vec<u8>,
+    timeout: Duration,
+    idle_since: Option<Instant>,
+    events: VecDeque<ScreenSaverEvent>,
+}
+
+/// A message received from the compositor
+struct Event {
+    object: u32,
+    opcode: u16,
+    args: Vec<u8>,
+}
+
+impl WaylandIdle {
+    /// Connects to the compositor in `$WAYLAND_DISPLAY` and asks to be notified once the user has
+    /// been idle for `timeout`.
+    pub fn new(timeout: Duration) -> AnyResult<WaylandIdle> {
+        let path = socket_path()?;
+        let socket = UnixStream::connect(&path).with_context(|| {
+            format!(
+                "Could not connect to Wayland compositor at {}",
+                path.display()
+            )
+        })?;
+
+        let mut idle = WaylandIdle {
+            socket,
+            buffer: Vec::new(),
+            timeout,
+            idle_since: None,
+            events: VecDeque::new(),
+        };
+
+        // Find the globals we need, using a sync callback to know when the compositor has sent
+        // all of them
+        idle.send(
+            DISPLAY_ID,
+            DISPLAY_GET_REGISTRY,
+            &Args::new().uint(REGISTRY_ID),
+        )?;
+        idle.send(
+            DISPLAY_ID,
+            DISPLAY_SYNC,
+            &Args::new().uint(SYNC_CALLBACK_ID),
+        )?;
+        let mut notifier = None;
+        let mut seat = None;
+        loop {
+            let event = idle.read_event()?;
+            match (event.object, event.opcode) {
+                (REGISTRY_ID, REGISTRY_GLOBAL) => {
+                    let mut args = ArgReader::new(&event.args);
+                    let name = args.uint()?;
+                    let interface = args.string()?;
+                    if interface == NOTIFIER_INTERFACE {
+                        notifier = Some(name);
+                    } else if interface == SEAT_INTERFACE && seat.is_none() {
+                        seat = Some(name);
+                    }
+                }
+                (SYNC_CALLBACK_ID, CALLBACK_DONE) => break,
+                _ => idle.handle(event)?,
+            }
+        }
+
+        let notifier = notifier
+            .ok_or_else(|| anyhow!("Wayland compositor does not support {}", NOTIFIER_INTERFACE))?;
+        let seat = seat.ok_or_else(|| anyhow!("Wayland compositor has no seat"))?;
+
+        idle.bind(notifier, NOTIFIER_INTERFACE, NOTIFIER_ID)?;
+        idle.bind(seat, SEAT_INTERFACE, SEAT_ID)?;
+        let timeout_ms = timeout.as_millis().try_into().unwrap_or(u32::MAX);
+        idle.send(
+            NOTIFIER_ID,
+            NOTIFIER_GET_IDLE_NOTIFICATION,
+            &Args::new()
+                .uint(NOTIFICATION_ID)
+                .uint(timeout_ms)
+                .uint(SEAT_ID),
+        )?;
+
+        idle.socket
+            .set_nonblocking(true)
+            .context("Could not make Wayland socket non-blocking")?;
+        Ok(idle)
+    }
+
+    fn bind(&mut self, name: u32, interface: &str, id: u32) -> AnyResult<()> {
+        self.send(
+            REGISTRY_ID,
+            REGISTRY_BIND,
+            &Args::new().uint(name).string(interface).uint(1).uint(id),
+        )
+    }
+
+    fn send(&mut self, object: u32, opcode: u16, args: &Args) -> AnyResult<()> {
+        self.socket
+            .write_all(&encode(object, opcode, args))
+            .context("Could not send Wayland request")
+    }
+
+    /// Reads the next event, blocking if the socket is blocking and none are buffered. Returns
+    /// `None` if the socket is non-blocking and there's nothing to read.
+    fn try_read_event(&mut self) -> AnyResult<Option<Event>> {
+        loop {
+            if let Some(event) = self.parse_event() {
+                return Ok(Some(event));
+            }
+
+            let mut chunk = [0; 4096];
+            match self.socket.read(&mut chunk) {
+                Ok(0) => bail!("Wayland compositor closed the connection"),
+                Ok(len) => self.buffer.extend_from_slice(&chunk[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(e).context("Could not read from Wayland compositor"),
+            }
+        }
+    }
+
+    fn read_event(&mut self) -> AnyResult<Event> {
+        self.try_read_event()?
+            .ok_or_else(|| anyhow!("No Wayland event available"))
+    }
+
+    fn parse_event(&mut self) -> Option<Event> {
+        if self.buffer.len() < 8 {
+            return None;
+        }
+        let object = u32::from_ne_bytes(self.buffer[0..4].try_into().unwrap());
+        let header = u32::from_ne_bytes(self.buffer[4..8].try_into().unwrap());
+        // The size includes the header, so anything smaller is malformed
+        let size = ((header >> 16) as usize).max(8);
+        if self.buffer.len() < size {
+            return None;
+        }
+
+        let args = self.buffer[8..size].to_vec();
+        self.buffer.drain(..size);
+        Some(Event {
+            object,
+            opcode: (header & 0xffff) as u16,
+            args,
+        })
+    }
+
+    fn handle(&mut self, event: Event) -> AnyResult<()> {
+        match (event.object, event.opcode) {
+            (DISPLAY_ID, DISPLAY_ERROR) => {
+                let mut args = ArgReader::new(&event.args);
+                let object = args.uint()?;
+                let code = args.uint()?;
+                let message = args.string()?;
+                bail!(
+                    "Wayland protocol error {} on object {}: {}",
+                    code,
+                    object,
+                    message
+                );
+            }
+            (NOTIFICATION_ID, NOTIFICATION_IDLED) => {
+                self.idle_since = Some(Instant::now());
+                self.events.push_back(ScreenSaverEvent::On);
+            }
+            (NOTIFICATION_ID, NOTIFICATION_RESUMED) => {
+                self.idle_since = None;
+                self.events.push_back(ScreenSaverEvent::Off);
+            }
+            // Everything else, like seat capabilities, is irrelevant
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl IdleSource for WaylandIdle {
+    fn query_state(&self) -> AnyResult<ScreenSaverEvent> {
+        Ok(if self.idle_since.is_some() {
+            ScreenSaverEvent::On
+        } else {
+            ScreenSaverEvent::Off
+        })
+    }
+
+    fn poll_event(&mut self) -> AnyResult<Option<ScreenSaverEvent>> {
+        while let Some(event) = self.try_read_event()? {
+            self.handle(event)?;
+        }
+        Ok(self.events.pop_front())
+    }
+
+    fn idle_time(&self) -> AnyResult<Duration> {
+        // The compositor only says when the timeout passes, so this is an estimate
+        Ok(match self.idle_since {
+            Some(since) => self.timeout + since.elapsed(),
+            None => Duration::from_secs(0),
+        })
+    }
+}
+
+fn socket_path() -> AnyResult<PathBuf> {
+    let display = env::var_os("WAYLAND_DISPLAY").unwrap_or_else(|| "wayland-0".into());
+    let display = PathBuf::from(display);
+    if display.is_absolute() {
+        return Ok(display);
+    }
+
+    let runtime_dir =
+        env::var_os("XDG_RUNTIME_DIR").ok_or_else(|| anyhow!("XDG_RUNTIME_DIR is not set"))?;
+    Ok(PathBuf::from(runtime_dir).join(display))
+}
+
+/// Encodes a message in the Wayland wire format: the object ID, then the message size and opcode,
+/// then the arguments.
+fn encode(object: u32, opcode: u16, args: &Args) -> Vec<u8> {
+    let size = 8 + args.0.len() as u32;
+    let mut message = Vec::with_capacity(size as usize);
+    message.extend_from_slice(&object.to_ne_bytes());
+    message.extend_from_slice(&((size << 16) | u32::from(opcode)).to_ne_bytes());
+    message.extend_from_slice(&args.0);
+    message
+}
+
+/// Builder for request arguments in the Wayland wire format
+struct Args(Vec<u8>);
+
+impl Args {
+    fn new() -> Args {
+        Args(Vec::new())
+    }
+
+    fn uint(mut self, value: u32) -> Args {
+        self.0.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
+
+    fn string(mut self, value: &str) -> Args {
+        // Strings are length-prefixed, including a NUL terminator, and padded to 32 bits
+        let len = value.len() + 1;
+        self.0.extend_from_slice(&(len as u32).to_ne_bytes());
+        self.0.extend_from_slice(value.as_bytes());
+        self.0.resize(self.0.len() + padded(len) - value.len(), 0);
+        self
+    }
+}
+
+/// Reader for event arguments in the Wayland wire format
+struct ArgReader<'a> {
+    args: &'a [u8],
+}
+
+impl<'a> ArgReader<'a> {
+    fn new(args: &'a [u8]) -> ArgReader<'a> {
+        ArgReader { args }
+    }
+
+    fn uint(&mut self) -> AnyResult<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_ne_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> AnyResult<String> {
+        let len = self.uint()? as usize;
+        let bytes = self.take(padded(len))?;
+        let value = &bytes[..len.saturating_sub(1)];
+        Ok(String::from_utf8_lossy(value).into_owned())
+    }
+
+    fn take(&mut self, len: usize) -> AnyResult<&'a [u8]> {
+        if self.args.len() < len {
+            bail!("Truncated Wayland event");
+        }
+        let (taken, rest) = self.args.split_at(len);
+        self.args = rest;
+        Ok(taken)
+    }
+}
+
+/// Rounds up to a multiple of 4 bytes
+fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An idle source connected to a fake compositor, which is the other end of the socket
+    fn connect() -> (WaylandIdle, UnixStream) {
+        let (socket, compositor) = UnixStream::pair().unwrap();
+        socket.set_nonblocking(true).unwrap();
+        let idle = WaylandIdle {
+            socket,
+            buffer: Vec::new(),
+            timeout: Duration::from_secs(60),
+            idle_since: None,
+            events: VecDeque::new(),
+        };
+        (idle, compositor)
+    }
+
+    #[test]
+    fn strings_are_padded() {
+        // Length prefix, then the string and its NUL terminator padded to 32 bits
+        let args = Args::new().string("seat");
+        assert_eq!(args.0.len(), 4 + 8);
+        assert_eq!(&args.0[..4], &5u32.to_ne_bytes());
+        assert_eq!(&args.0[4..], b"seat\0\0\0\0");
+
+        let args = Args::new().string("abc");
+        assert_eq!(args.0.len(), 4 + 4);
+        assert_eq!(&args.0[4..], b"abc\0");
+    }
+
+    #[test]
+    fn args_round_trip() {
+        for value in ["", "a", "ab", "abc", "wl_seat", NOTIFIER_INTERFACE].iter() {
+            let args = Args::new().uint(1).string(value).uint(2);
+            let mut reader = ArgReader::new(&args.0);
+            assert_eq!(reader.uint().unwrap(), 1);
+            assert_eq!(reader.string().unwrap(), *value);
+            assert_eq!(reader.uint().unwrap(), 2);
+            assert!(reader.uint().is_err());
+        }
+    }
+
+    #[test]
+    fn truncated_string_is_an_error() {
+        let args = Args::new().string("wl_seat");
+        let mut reader = ArgReader::new(&args.0[..6]);
+        assert!(reader.string().is_err());
+    }
+
+    #[test]
+    fn parses_partial_and_multiple_events() {
+        let (mut idle, _compositor) = connect();
+        let first = encode(
+            REGISTRY_ID,
+            REGISTRY_GLOBAL,
+            &Args::new().uint(7).string("wl_seat"),
+        );
+        let second = encode(SYNC_CALLBACK_ID, CALLBACK_DONE, &Args::new().uint(0));
+
+        // Nothing is parsed until the whole message has arrived
+        idle.buffer.extend_from_slice(&first[..4]);
+        assert!(idle.parse_event().is_none());
+        idle.buffer.extend_from_slice(&first[4..first.len() - 1]);
+        assert!(idle.parse_event().is_none());
+
+        // The rest of the first message arrives with all of the second
+        idle.buffer.push(first[first.len() - 1]);
+        idle.buffer.extend_from_slice(&second);
+
+        let event = idle.parse_event().unwrap();
+        assert_eq!((event.object, event.opcode), (REGISTRY_ID, REGISTRY_GLOBAL));
+        let mut args = ArgReader::new(&event.args);
+        assert_eq!(args.uint().unwrap(), 7);
+        assert_eq!(args.string().unwrap(), "wl_seat");
+
+        let event = idle.parse_event().unwrap();
+        assert_eq!(
+            (event.object, event.opcode),
+            (SYNC_CALLBACK_ID, CALLBACK_DONE)
+        );
+        assert!(idle.parse_event().is_none());
+        assert!(idle.buffer.is_empty());
+    }
+
+    #[test]
+    fn reports_idle_notifications() {
+        let (mut idle, mut compositor) = connect();
+        assert_eq!(idle.poll_event().unwrap(), None);
+
+        let mut messages = encode(NOTIFICATION_ID, NOTIFICATION_IDLED, &Args::new());
+        messages.extend(encode(NOTIFICATION_ID, NOTIFICATION_RESUMED, &Args::new()));
+        compositor.write_all(&messages).unwrap();
+
+        assert_eq!(idle.poll_event().unwrap(), Some(ScreenSaverEvent::On));
+        assert_eq!(idle.poll_event().unwrap(), Some(ScreenSaverEvent::Off));
+        assert_eq!(idle.poll_event().unwrap(), None);
+        assert_eq!(idle.query_state().unwrap(), ScreenSaverEvent::Off);
+    }
+
+    #[test]
+    fn protocol_error_is_reported() {
+        let (mut idle, mut compositor) = connect();
+        let args = Args::new().uint(NOTIFIER_ID).uint(3).string("bad request");
+        compositor
+            .write_all(&encode(DISPLAY_ID, DISPLAY_ERROR, &args))
+            .unwrap();
+
+        let err = idle.poll_event().unwrap_err();
+        assert!(err.to_string().contains("bad request"), "{}", err);
+    }
+}
@@ -0,0 +1,57 @@
+//! Lists sessions, users, and seats, similar to `loginctl list-sessions`, `list-users`, and
+//! `list-seats`.
+//!
+//! Run with `cargo run --example loginctl`.
+
+use std::error::Error;
+
+use dbus::blocking::Connection;
+
+use desk_logind::Logind;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let conn = Connection::new_system()?;
+    let logind = Logind::new(&conn);
+
+    let sessions = logind.list_sessions()?;
+    if sessions.is_empty() {
+        println!("No sessions.");
+    } else {
+        println!(
+            "{:>8} {:>6} {:<12} {:<8} {:<8} {:<8}",
+            "SESSION", "UID", "USER", "SEAT", "TTY", "STATE"
+        );
+        for info in &sessions {
+            // The listing only has the basics, so look up the rest from the session itself
+            let (tty, state) = match logind.session(&info.id).and_then(|s| s.properties()) {
+                Ok(props) => (props.tty, props.state),
+                // The session may have ended since it was listed
+                Err(e) if e.is_no_such_session() => continue,
+                Err(e) => return Err(e.into()),
+            };
+            println!(
+                "{:>8} {:>6} {:<12} {:<8} {:<8} {:<8}",
+                info.id, info.uid, info.user, info.seat, tty, state
+            );
+        }
+        println!("\n{} sessions listed.", sessions.len());
+    }
+
+    let users = logind.list_users()?;
+    println!();
+    println!("{:>6} {:<12}", "UID", "USER");
+    for user in &users {
+        println!("{:>6} {:<12}", user.uid, user.name);
+    }
+    println!("\n{} users listed.", users.len());
+
+    let seats = logind.list_seats()?;
+    println!();
+    println!("SEAT");
+    for seat in &seats {
+        println!("{}", seat);
+    }
+    println!("\n{} seats listed.", seats.len());
+
+    Ok(())
+}
@@ -1,2 +1,4 @@
 pub mod manager;
+pub mod seat;
 pub mod session;
+pub mod user;
@@ -0,0 +1,118 @@
+#![allow(clippy::all)]
+// This code was autogenerated with `dbus-codegen-rust -s -d org.freedesktop.login1 -p /org/freedesktop/login1/seat/self -c blocking -m None -f org.freedesktop.login1.Seat -o desk-logind/src/api/seat.rs`, see https://github.com/diwic/dbus-rs
+
+use dbus;
+use dbus::blocking;
+
+pub trait OrgFreedesktopLogin1Seat {
+    fn terminate(&self) -> Result<(), dbus::Error>;
+    fn activate_session(&self, arg0: &str) -> Result<(), dbus::Error>;
+    fn switch_to(&self, arg0: u32) -> Result<(), dbus::Error>;
+    fn switch_to_next(&self) -> Result<(), dbus::Error>;
+    fn switch_to_previous(&self) -> Result<(), dbus::Error>;
+    fn id(&self) -> Result<String, dbus::Error>;
+    fn active_session(&self) -> Result<(String, dbus::Path<'static>), dbus::Error>;
+    fn can_multi_session(&self) -> Result<bool, dbus::Error>;
+    fn can_tty(&self) -> Result<bool, dbus::Error>;
+    fn can_graphical(&self) -> Result<bool, dbus::Error>;
+    fn sessions(&self) -> Result<Vec<(String, dbus::Path<'static>)>, dbus::Error>;
+    fn idle_hint(&self) -> Result<bool, dbus::Error>;
+    fn idle_since_hint(&self) -> Result<u64, dbus::Error>;
+    fn idle_since_hint_monotonic(&self) -> Result<u64, dbus::Error>;
+}
+
+impl<'a, C: ::std::ops::Deref<Target = blocking::Connection>> OrgFreedesktopLogin1Seat
+    for blocking::Proxy<'a, C>
+{
+    fn terminate(&self) -> Result<(), dbus::Error> {
+        self.method_call("org.freedesktop.login1.Seat", "Terminate", ())
+    }
+
+    fn activate_session(&self, arg0: &str) -> Result<(), dbus::Error> {
+        self.method_call("org.freedesktop.login1.Seat", "ActivateSession", (arg0,))
+    }
+
+    fn switch_to(&self, arg0: u32) -> Result<(), dbus::Error> {
+        self.method_call("org.freedesktop.login1.Seat", "SwitchTo", (arg0,))
+    }
+
+    fn switch_to_next(&self) -> Result<(), dbus::Error> {
+        self.method_call("org.freedesktop.login1.Seat", "SwitchToNext", ())
+    }
+
+    fn switch_to_previous(&self) -> Result<(), dbus::Error> {
+        self.method_call("org.freedesktop.login1.Seat", "SwitchToPrevious", ())
+    }
+
+    fn id(&self) -> Result<String, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.Seat",
+            "Id",
+        )
+    }
+
+    fn active_session(&self) -> Result<(String, dbus::Path<'static>), dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.Seat",
+            "ActiveSession",
+        )
+    }
+
+    fn can_multi_session(&self) -> Result<bool, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.Seat",
+            "CanMultiSession",
+        )
+    }
+
+    fn can_tty(&self) -> Result<bool, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.Seat",
+            "CanTTY",
+        )
+    }
+
+    fn can_graphical(&self) -> Result<bool, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.Seat",
+            "CanGraphical",
+        )
+    }
+
+    fn sessions(&self) -> Result<Vec<(String, dbus::Path<'static>)>, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.Seat",
+            "Sessions",
+        )
+    }
+
+    fn idle_hint(&self) -> Result<bool, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.Seat",
+            "IdleHint",
+        )
+    }
+
+    fn idle_since_hint(&self) -> Result<u64, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.Seat",
+            "IdleSinceHint",
+        )
+    }
+
+    fn idle_since_hint_monotonic(&self) -> Result<u64, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.Seat",
+            "IdleSinceHintMonotonic",
+        )
+    }
+}
@@ -0,0 +1,157 @@
+#![allow(clippy::all)]
+// This code was autogenerated with `dbus-codegen-rust -s -d org.freedesktop.login1 -p /org/freedesktop/login1/user/self -c blocking -m None -f org.freedesktop.login1.User -o desk-logind/src/api/user.rs`, see https://github.com/diwic/dbus-rs
+
+use dbus;
+use dbus::blocking;
+
+pub trait OrgFreedesktopLogin1User {
+    fn terminate(&self) -> Result<(), dbus::Error>;
+    fn kill(&self, arg0: i32) -> Result<(), dbus::Error>;
+    fn uid(&self) -> Result<u32, dbus::Error>;
+    fn gid(&self) -> Result<u32, dbus::Error>;
+    fn name(&self) -> Result<String, dbus::Error>;
+    fn timestamp(&self) -> Result<u64, dbus::Error>;
+    fn timestamp_monotonic(&self) -> Result<u64, dbus::Error>;
+    fn runtime_path(&self) -> Result<String, dbus::Error>;
+    fn service(&self) -> Result<String, dbus::Error>;
+    fn slice(&self) -> Result<String, dbus::Error>;
+    fn display(&self) -> Result<(String, dbus::Path<'static>), dbus::Error>;
+    fn state(&self) -> Result<String, dbus::Error>;
+    fn sessions(&self) -> Result<Vec<(String, dbus::Path<'static>)>, dbus::Error>;
+    fn idle_hint(&self) -> Result<bool, dbus::Error>;
+    fn idle_since_hint(&self) -> Result<u64, dbus::Error>;
+    fn idle_since_hint_monotonic(&self) -> Result<u64, dbus::Error>;
+    fn linger(&self) -> Result<bool, dbus::Error>;
+}
+
+impl<'a, C: ::std::ops::Deref<Target = blocking::Connection>> OrgFreedesktopLogin1User
+    for blocking::Proxy<'a, C>
+{
+    fn terminate(&self) -> Result<(), dbus::Error> {
+        self.method_call("org.freedesktop.login1.User", "Terminate", ())
+    }
+
+    fn kill(&self, arg0: i32) -> Result<(), dbus::Error> {
+        self.method_call("org.freedesktop.login1.User", "Kill", (arg0,))
+    }
+
+    fn uid(&self) -> Result<u32, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "UID",
+        )
+    }
+
+    fn gid(&self) -> Result<u32, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "GID",
+        )
+    }
+
+    fn name(&self) -> Result<String, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "Name",
+        )
+    }
+
+    fn timestamp(&self) -> Result<u64, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "Timestamp",
+        )
+    }
+
+    fn timestamp_monotonic(&self) -> Result<u64, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "TimestampMonotonic",
+        )
+    }
+
+    fn runtime_path(&self) -> Result<String, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "RuntimePath",
+        )
+    }
+
+    fn service(&self) -> Result<String, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "Service",
+        )
+    }
+
+    fn slice(&self) -> Result<String, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "Slice",
+        )
+    }
+
+    fn display(&self) -> Result<(String, dbus::Path<'static>), dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "Display",
+        )
+    }
+
+    fn state(&self) -> Result<String, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "State",
+        )
+    }
+
+    fn sessions(&self) -> Result<Vec<(String, dbus::Path<'static>)>, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "Sessions",
+        )
+    }
+
+    fn idle_hint(&self) -> Result<bool, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "IdleHint",
+        )
+    }
+
+    fn idle_since_hint(&self) -> Result<u64, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "IdleSinceHint",
+        )
+    }
+
+    fn idle_since_hint_monotonic(&self) -> Result<u64, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "IdleSinceHintMonotonic",
+        )
+    }
+
+    fn linger(&self) -> Result<bool, dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+            &self,
+            "org.freedesktop.login1.User",
+            "Linger",
+        )
+    }
+}
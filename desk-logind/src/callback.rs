@@ -0,0 +1,62 @@
+//! Error handling for signal callbacks
+
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+
+use log::error;
+
+/// Error returned by a signal callback. Any error type can be converted into this with `?`.
+pub type CallbackError = Box<dyn Error + Send + Sync>;
+
+/// Result returned by signal callbacks registered with the `on_*` methods
+pub type CallbackResult = Result<(), CallbackError>;
+
+type ErrorSink = Arc<dyn Fn(&'static str, &CallbackError) + Send + Sync>;
+
+static ERROR_SINK: RwLock<Option<ErrorSink>> = RwLock::new(None);
+
+/// Set what happens when a signal callback fails. The sink is called with the name of the signal,
+/// such as `PrepareForSleep`, and the callback's error. By default, errors are logged at the error
+/// level.
+///
+/// Callbacks run from inside D-Bus message processing, so there's no caller to return errors to.
+/// A sink can be used to collect them instead, such as to stop an `EventLoop`. The sink may itself
+/// call `set_error_sink`, which takes effect for the next error.
+pub fn set_error_sink<F: Fn(&'static str, &CallbackError) + Send + Sync + 'static>(sink: F) {
+    *ERROR_SINK.write().unwrap() = Some(Arc::new(sink));
+}
+
+/// Pass a failed callback result on to the error sink.
+pub(crate) fn report(signal: &'static str, result: CallbackResult) {
+    if let Err(e) = result {
+        // Don't hold the lock while the sink runs, in case it replaces itself
+        let sink = ERROR_SINK.read().unwrap().clone();
+        match sink {
+            Some(sink) => sink(signal, &e),
+            None => error!("Handling {} failed: {:?}", signal, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn sink_can_replace_itself() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        set_error_sink(|_, _| {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            set_error_sink(|_, _| {
+                CALLS.fetch_add(10, Ordering::SeqCst);
+            });
+        });
+
+        report("First", Err("first".into()));
+        report("Second", Err("second".into()));
+        assert_eq!(CALLS.load(Ordering::SeqCst), 11);
+        *ERROR_SINK.write().unwrap() = None;
+    }
+}
@@ -0,0 +1,93 @@
+//! Devices delegated to a session controller by logind
+
+use std::fmt;
+use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
+
+use dbus::arg::OwnedFd;
+
+/// Why logind paused a device, as reported by the `PauseDevice` signal
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub enum PauseKind {
+    /// The device is being paused, and logind is waiting for the controller to acknowledge it
+    /// with `Session::pause_device_complete`.
+    Pause,
+    /// The device was already paused by logind without waiting for the controller.
+    Force,
+    /// The device was removed.
+    Gone,
+}
+
+impl PauseKind {
+    pub(crate) fn from_signal(kind: &str) -> PauseKind {
+        match kind {
+            "pause" => PauseKind::Pause,
+            "gone" => PauseKind::Gone,
+            // Anything else doesn't expect an acknowledgement, so treat it like a forced pause
+            _ => PauseKind::Force,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PauseKind::Pause => "pause",
+            PauseKind::Force => "force",
+            PauseKind::Gone => "gone",
+        }
+    }
+}
+
+impl fmt::Display for PauseKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// RAII handle on a device file descriptor obtained with `Session::take_device`. The file
+/// descriptor is closed when this is dropped. Note that this does not release the device in
+/// logind - use `Session::release_device` for that.
+#[derive(Debug)]
+pub struct DeviceFd {
+    major: u32,
+    minor: u32,
+    fd: OwnedFd,
+}
+
+impl DeviceFd {
+    pub(crate) fn new(major: u32, minor: u32, fd: OwnedFd) -> DeviceFd {
+        DeviceFd { major, minor, fd }
+    }
+
+    /// Major number of the device
+    pub fn major(&self) -> u32 {
+        self.major
+    }
+
+    /// Minor number of the device
+    pub fn minor(&self) -> u32 {
+        self.minor
+    }
+}
+
+impl AsRawFd for DeviceFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for DeviceFd {
+    fn into_raw_fd(self) -> RawFd {
+        self.fd.into_fd()
+    }
+}
+
+impl fmt::Display for DeviceFd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{} (fd {})",
+            self.major,
+            self.minor,
+            self.fd.as_raw_fd()
+        )
+    }
+}
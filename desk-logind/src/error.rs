@@ -1,44 +1,198 @@
 //! Logind error type
-use std::backtrace::Backtrace;
-
 use dbus::Error as DBusError;
 use thiserror::Error;
 
+/// Where a [`LogindError`] was created. With the `backtrace` feature (on by default), this is a
+/// [`std::backtrace::Backtrace`], captured if `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` is set.
+/// Without it, this is an empty placeholder, so that `LogindError` has the same shape either way.
+// Not named `Backtrace`, because thiserror would then implement the nightly-only
+// `Error::backtrace` for it
+#[cfg(feature = "backtrace")]
+pub type ErrorBacktrace = std::backtrace::Backtrace;
+
+/// Where a [`LogindError`] was created. This is an empty placeholder, since the `backtrace`
+/// feature is disabled.
+#[cfg(not(feature = "backtrace"))]
+#[derive(Debug)]
+pub struct ErrorBacktrace(());
+
+#[cfg(not(feature = "backtrace"))]
+impl ErrorBacktrace {
+    fn capture() -> ErrorBacktrace {
+        ErrorBacktrace(())
+    }
+}
+
+#[cfg(not(feature = "backtrace"))]
+impl std::fmt::Display for ErrorBacktrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("disabled backtrace")
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum LogindError {
-    #[error("XDG_SESSION_ID not set")]
-    NoSessionId { backtrace: Backtrace },
+    #[error("XDG_SESSION_ID not set and this process is not part of a session")]
+    NoSessionId { backtrace: ErrorBacktrace },
 
     #[error("Could not subscribe to {signal}")]
     MatchFailed {
         signal: &'static str,
         #[source]
         source: DBusError,
-        backtrace: Backtrace,
+        backtrace: ErrorBacktrace,
+    },
+
+    #[error("Property {property} is missing or has an unexpected type")]
+    InvalidProperty {
+        property: &'static str,
+        backtrace: ErrorBacktrace,
+    },
+
+    #[error("Unrecognized {kind} `{value}`")]
+    UnrecognizedValue {
+        kind: &'static str,
+        value: String,
+        backtrace: ErrorBacktrace,
+    },
+
+    #[error("No such session")]
+    NoSuchSession {
+        #[source]
+        source: DBusError,
+        backtrace: ErrorBacktrace,
+    },
+
+    #[error("No such seat")]
+    NoSuchSeat {
+        #[source]
+        source: DBusError,
+        backtrace: ErrorBacktrace,
+    },
+
+    #[error("No such user")]
+    NoSuchUser {
+        #[source]
+        source: DBusError,
+        backtrace: ErrorBacktrace,
+    },
+
+    #[error("Not authorized")]
+    NotAuthorized {
+        #[source]
+        source: DBusError,
+        backtrace: ErrorBacktrace,
+    },
+
+    #[error("Sleep operation not supported")]
+    SleepNotSupported {
+        #[source]
+        source: DBusError,
+        backtrace: ErrorBacktrace,
+    },
+
+    #[error("Operation not supported")]
+    NotSupported {
+        #[source]
+        source: DBusError,
+        backtrace: ErrorBacktrace,
+    },
+
+    #[error("Another operation is already in progress")]
+    OperationInProgress {
+        #[source]
+        source: DBusError,
+        backtrace: ErrorBacktrace,
     },
 
     // TODO: add more specific error cases as they come up
     #[error("D-Bus operation failed")]
     DBusError {
-        #[from]
         #[source]
         source: DBusError,
-        backtrace: Backtrace,
+        backtrace: ErrorBacktrace,
     },
 
+    #[error("No events given to inhibit")]
+    EmptyEventSet { backtrace: ErrorBacktrace },
+
     #[error("{message}")]
     InhibitorFileError {
         message: String,
         #[source]
         source: nix::Error,
-        backtrace: Backtrace,
+        backtrace: ErrorBacktrace,
     },
 }
 
 impl LogindError {
+    /// The name of the underlying D-Bus error, if this error came from a D-Bus call.
+    pub fn dbus_error_name(&self) -> Option<&str> {
+        match self {
+            LogindError::MatchFailed { source, .. }
+            | LogindError::NoSuchSession { source, .. }
+            | LogindError::NoSuchSeat { source, .. }
+            | LogindError::NoSuchUser { source, .. }
+            | LogindError::NotAuthorized { source, .. }
+            | LogindError::SleepNotSupported { source, .. }
+            | LogindError::NotSupported { source, .. }
+            | LogindError::OperationInProgress { source, .. }
+            | LogindError::DBusError { source, .. } => source.name(),
+            _ => None,
+        }
+    }
+
+    /// Checks if the operation failed because the caller wasn't authorized to perform it. This
+    /// includes the case where PolicyKit would have needed to prompt the user, but the operation
+    /// wasn't interactive.
+    pub fn is_not_authorized(&self) -> bool {
+        matches!(self.dbus_error_name(), Some(name) if NOT_AUTHORIZED_ERRORS.contains(&name))
+    }
+
+    /// Checks if the operation failed because the requested session doesn't exist.
+    pub fn is_no_such_session(&self) -> bool {
+        matches!(self.dbus_error_name(), Some(name) if NO_SUCH_SESSION_ERRORS.contains(&name))
+    }
+
+    /// Checks if the operation failed because the requested seat doesn't exist.
+    pub fn is_no_such_seat(&self) -> bool {
+        self.dbus_error_name() == Some(NO_SUCH_SEAT_ERROR)
+    }
+
+    /// Checks if the operation failed because the requested user isn't logged in.
+    pub fn is_no_such_user(&self) -> bool {
+        self.dbus_error_name() == Some(NO_SUCH_USER_ERROR)
+    }
+
+    /// Checks if the operation failed because it isn't supported on this system.
+    pub fn is_not_supported(&self) -> bool {
+        matches!(self.dbus_error_name(), Some(name) if NOT_SUPPORTED_ERRORS.contains(&name))
+    }
+
+    /// Where the error was created. thiserror can only expose this through
+    /// `std::error::Error` on nightly, so it's available here instead.
+    pub fn backtrace(&self) -> &ErrorBacktrace {
+        match self {
+            LogindError::NoSessionId { backtrace }
+            | LogindError::MatchFailed { backtrace, .. }
+            | LogindError::InvalidProperty { backtrace, .. }
+            | LogindError::UnrecognizedValue { backtrace, .. }
+            | LogindError::NoSuchSession { backtrace, .. }
+            | LogindError::NoSuchSeat { backtrace, .. }
+            | LogindError::NoSuchUser { backtrace, .. }
+            | LogindError::NotAuthorized { backtrace, .. }
+            | LogindError::SleepNotSupported { backtrace, .. }
+            | LogindError::NotSupported { backtrace, .. }
+            | LogindError::OperationInProgress { backtrace, .. }
+            | LogindError::DBusError { backtrace, .. }
+            | LogindError::EmptyEventSet { backtrace }
+            | LogindError::InhibitorFileError { backtrace, .. } => backtrace,
+        }
+    }
+
     pub fn no_session_id() -> LogindError {
         LogindError::NoSessionId {
-            backtrace: Backtrace::capture(),
+            backtrace: ErrorBacktrace::capture(),
         }
     }
 
@@ -46,7 +200,28 @@ impl LogindError {
         LogindError::MatchFailed {
             signal,
             source: error,
-            backtrace: Backtrace::capture(),
+            backtrace: ErrorBacktrace::capture(),
+        }
+    }
+
+    pub fn invalid_property(property: &'static str) -> LogindError {
+        LogindError::InvalidProperty {
+            property,
+            backtrace: ErrorBacktrace::capture(),
+        }
+    }
+
+    pub fn unrecognized_value(kind: &'static str, value: String) -> LogindError {
+        LogindError::UnrecognizedValue {
+            kind,
+            value,
+            backtrace: ErrorBacktrace::capture(),
+        }
+    }
+
+    pub fn empty_event_set() -> LogindError {
+        LogindError::EmptyEventSet {
+            backtrace: ErrorBacktrace::capture(),
         }
     }
 
@@ -54,7 +229,54 @@ impl LogindError {
         LogindError::InhibitorFileError {
             message,
             source: error,
-            backtrace: Backtrace::capture(),
+            backtrace: ErrorBacktrace::capture(),
+        }
+    }
+}
+
+const NO_SUCH_SESSION_ERRORS: &[&str] = &[
+    "org.freedesktop.login1.NoSuchSession",
+    "org.freedesktop.login1.NoSessionForPID",
+];
+
+const NO_SUCH_SEAT_ERROR: &str = "org.freedesktop.login1.NoSuchSeat";
+
+const NO_SUCH_USER_ERROR: &str = "org.freedesktop.login1.NoSuchUser";
+
+const NOT_AUTHORIZED_ERRORS: &[&str] = &[
+    "org.freedesktop.DBus.Error.AccessDenied",
+    "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired",
+];
+
+const NOT_SUPPORTED_ERRORS: &[&str] = &[
+    "org.freedesktop.login1.SleepVerbNotSupported",
+    "org.freedesktop.DBus.Error.NotSupported",
+];
+
+/// Converts D-Bus errors into the most specific `LogindError` variant possible, based on the D-Bus
+/// error name. Errors that aren't recognized become `LogindError::DBusError`.
+impl From<DBusError> for LogindError {
+    fn from(source: DBusError) -> LogindError {
+        let backtrace = ErrorBacktrace::capture();
+        match source.name() {
+            Some(name) if NO_SUCH_SESSION_ERRORS.contains(&name) => {
+                LogindError::NoSuchSession { source, backtrace }
+            }
+            Some(NO_SUCH_SEAT_ERROR) => LogindError::NoSuchSeat { source, backtrace },
+            Some(NO_SUCH_USER_ERROR) => LogindError::NoSuchUser { source, backtrace },
+            Some(name) if NOT_AUTHORIZED_ERRORS.contains(&name) => {
+                LogindError::NotAuthorized { source, backtrace }
+            }
+            Some("org.freedesktop.login1.SleepVerbNotSupported") => {
+                LogindError::SleepNotSupported { source, backtrace }
+            }
+            Some("org.freedesktop.DBus.Error.NotSupported") => {
+                LogindError::NotSupported { source, backtrace }
+            }
+            Some("org.freedesktop.login1.OperationInProgress") => {
+                LogindError::OperationInProgress { source, backtrace }
+            }
+            _ => LogindError::DBusError { source, backtrace },
         }
     }
 }
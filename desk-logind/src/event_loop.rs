@@ -0,0 +1,91 @@
+//! Helper for driving logind signal callbacks
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use dbus::blocking::Connection;
+
+use crate::error::LogindError;
+use crate::signal::SignalToken;
+
+/// Drives a D-Bus connection so that callbacks registered with the `on_*` methods are run.
+///
+/// The event loop holds on to the `SignalToken`s of the subscriptions added to it, so they stay
+/// registered until the loop finishes.
+pub struct EventLoop<'a> {
+    conn: &'a Connection,
+    tick: Duration,
+    subscriptions: Vec<SignalToken<'a>>,
+    stop: StopHandle,
+}
+
+impl<'a> EventLoop<'a> {
+    pub(crate) fn new(conn: &'a Connection) -> EventLoop<'a> {
+        EventLoop {
+            conn,
+            tick: Duration::from_millis(100),
+            subscriptions: Vec::new(),
+            stop: StopHandle::new(),
+        }
+    }
+
+    /// Set how long to wait for D-Bus messages on each iteration of the loop. This is also how
+    /// often the `run_with` callback is called. Defaults to 100ms.
+    pub fn tick(mut self, tick: Duration) -> EventLoop<'a> {
+        self.tick = tick;
+        self
+    }
+
+    /// Keep a signal subscription registered for as long as the event loop runs.
+    pub fn subscribe(mut self, token: SignalToken<'a>) -> EventLoop<'a> {
+        self.subscriptions.push(token);
+        self
+    }
+
+    /// Get a handle which can be used to stop the event loop, such as from inside a signal
+    /// callback.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Run the event loop until it's stopped or processing D-Bus messages fails.
+    pub fn run(self) -> Result<(), LogindError> {
+        self.run_with(|| Ok(()))
+    }
+
+    /// Run the event loop, calling `on_tick` after each batch of D-Bus messages is processed. The
+    /// loop ends when it's stopped, or if processing messages or `on_tick` fails.
+    pub fn run_with<E, F>(self, mut on_tick: F) -> Result<(), E>
+    where
+        E: From<LogindError>,
+        F: FnMut() -> Result<(), E>,
+    {
+        while !self.stop.is_stopped() {
+            self.conn
+                .process(self.tick)
+                .map_err(|e| E::from(LogindError::from(e)))?;
+            on_tick()?;
+        }
+        Ok(())
+    }
+}
+
+/// Handle for stopping an `EventLoop`. The loop finishes its current iteration before returning.
+#[derive(Debug, Clone)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    fn new() -> StopHandle {
+        StopHandle(Arc::new(AtomicBool::new(false)))
+    }
+
+    /// Ask the event loop to stop.
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Checks if the event loop has been asked to stop.
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
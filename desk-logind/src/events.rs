@@ -0,0 +1,94 @@
+//! A single stream of logind events, as an alternative to registering separate callbacks
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+use dbus::blocking::Connection;
+
+use crate::error::LogindError;
+use crate::signal::SignalToken;
+
+/// A logind signal, normalized into what it means for the caller.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum LogindEvent {
+    /// The system is about to go to sleep
+    SleepStart,
+    /// The system resumed from sleep
+    SleepEnd,
+    /// The current session was asked to lock its screen
+    SessionLocked,
+    /// The current session was asked to unlock its screen
+    SessionUnlocked,
+    /// The system is about to shut down or reboot
+    ShutdownScheduled,
+    /// A shutdown that was announced with `ShutdownScheduled` won't happen after all
+    ShutdownCancelled,
+}
+
+/// Iterator over logind events, returned by [`Logind::events`](crate::Logind::events).
+///
+/// Iterating processes D-Bus messages on the connection, blocking until the next event. This also
+/// runs any other callbacks registered on the connection. The signal subscriptions are removed
+/// when the iterator is dropped.
+pub struct Events<'a> {
+    conn: &'a Connection,
+    receiver: Receiver<LogindEvent>,
+    // Only held so that the subscriptions stay registered
+    _subscriptions: Vec<SignalToken<'a>>,
+}
+
+/// How long to wait for a D-Bus message in each step of blocking iteration
+const PROCESS_TIMEOUT: Duration = Duration::from_secs(60);
+
+impl<'a> Events<'a> {
+    pub(crate) fn new(
+        conn: &'a Connection,
+        receiver: Receiver<LogindEvent>,
+        subscriptions: Vec<SignalToken<'a>>,
+    ) -> Events<'a> {
+        Events {
+            conn,
+            receiver,
+            _subscriptions: subscriptions,
+        }
+    }
+
+    /// Wait up to `timeout` for the next event, returning `None` if there wasn't one. This is
+    /// useful for checking for events from a loop that also does other work.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Result<Option<LogindEvent>, LogindError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // Events from earlier messages may already be queued up
+            if let Some(event) = self.try_recv() {
+                return Ok(Some(event));
+            }
+            // Processing stops after any message, not just ones that turn into events
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            self.conn.process(deadline - now)?;
+        }
+    }
+
+    fn try_recv(&self) -> Option<LogindEvent> {
+        // The senders live in the subscriptions' callbacks, so they can't disconnect while this
+        // is around
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Iterator for Events<'_> {
+    type Item = Result<LogindEvent, LogindError>;
+
+    /// Block until the next event. Returns an error if processing D-Bus messages fails, and never
+    /// returns `None`.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_timeout(PROCESS_TIMEOUT) {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => (),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
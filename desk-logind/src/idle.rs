@@ -0,0 +1,77 @@
+//! Idle detection based on a session's idle hint, for when there's no display server to ask
+use std::time::{Duration, SystemTime};
+
+use crate::callback::{self, CallbackResult};
+use crate::error::LogindError;
+use crate::session::Session;
+
+/// Watches a session's `IdleHint` and `IdleSinceHint`, returned by
+/// [`Logind::watch_idle`](crate::Logind::watch_idle).
+///
+/// logind doesn't send a signal when a session has been idle for some amount of time, so this has
+/// to be polled, such as from [`EventLoop::run_with`](crate::EventLoop::run_with). The callback
+/// runs once the session has been idle for at least the threshold, and then not again until the
+/// session becomes active and goes idle again.
+pub struct IdleWatch<'a> {
+    session: Session<'a>,
+    threshold: Duration,
+    callback: Box<dyn FnMut(Duration) -> CallbackResult + 'a>,
+    /// When this first saw the session idle, in case logind doesn't say when it went idle
+    seen_idle: Option<SystemTime>,
+    /// Whether the callback already ran for the current idle period
+    fired: bool,
+}
+
+impl<'a> IdleWatch<'a> {
+    pub(crate) fn new<F: FnMut(Duration) -> CallbackResult + 'a>(
+        session: Session<'a>,
+        threshold: Duration,
+        callback: F,
+    ) -> IdleWatch<'a> {
+        IdleWatch {
+            session,
+            threshold,
+            callback: Box::new(callback),
+            seen_idle: None,
+            fired: false,
+        }
+    }
+
+    /// Check the session's idle hint, running the callback if the session has been idle for long
+    /// enough. The callback receives how long the session has been idle. Errors returned by the
+    /// callback are passed to the error sink (see [`set_error_sink`](crate::set_error_sink)).
+    ///
+    /// Returns an error if reading the idle hint fails.
+    pub fn poll(&mut self) -> Result<(), LogindError> {
+        if !self.session.idle_hint()? {
+            // Active again, so the next idle period gets its own callback
+            self.seen_idle = None;
+            self.fired = false;
+            return Ok(());
+        }
+        if self.fired {
+            return Ok(());
+        }
+
+        let now = SystemTime::now();
+        let seen_idle = *self.seen_idle.get_or_insert(now);
+        // If logind doesn't know when the session went idle, count from when this noticed
+        let since = self.session.idle_since()?.unwrap_or(seen_idle);
+        let idle_for = now.duration_since(since).unwrap_or_default();
+        if idle_for >= self.threshold {
+            self.fired = true;
+            callback::report("IdleHint", (self.callback)(idle_for));
+        }
+        Ok(())
+    }
+
+    /// Whether the callback has run for the current idle period.
+    pub fn fired(&self) -> bool {
+        self.fired
+    }
+
+    /// The session being watched.
+    pub fn session(&self) -> &Session<'a> {
+        &self.session
+    }
+}
@@ -1,15 +1,25 @@
 //! Model for systemd-logind inhibitor locks
 
 use std::fmt;
+use std::iter::FromIterator;
 use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
+use std::str::FromStr;
 
 use dbus::arg::OwnedFd;
+use nix::fcntl::{fcntl, FcntlArg};
 use nix::unistd;
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::error::LogindError;
 
 /// A logind event which can be inhibited (by taking an inhibitor lock)
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum InhibitEvent {
     Shutdown,
     Sleep,
@@ -21,6 +31,17 @@ pub enum InhibitEvent {
 }
 
 impl InhibitEvent {
+    /// All events which can be inhibited
+    pub const ALL: [InhibitEvent; 7] = [
+        InhibitEvent::Shutdown,
+        InhibitEvent::Sleep,
+        InhibitEvent::Idle,
+        InhibitEvent::HandlePowerKey,
+        InhibitEvent::HandleSuspendKey,
+        InhibitEvent::HandleHibernateKey,
+        InhibitEvent::HandleLidSwitch,
+    ];
+
     pub fn as_str(self) -> &'static str {
         match self {
             InhibitEvent::Shutdown => "shutdown",
@@ -32,6 +53,19 @@ impl InhibitEvent {
             InhibitEvent::HandleLidSwitch => "handle-lid-switch",
         }
     }
+
+    pub(crate) fn from_name(name: &str) -> Option<InhibitEvent> {
+        match name {
+            "shutdown" => Some(InhibitEvent::Shutdown),
+            "sleep" => Some(InhibitEvent::Sleep),
+            "idle" => Some(InhibitEvent::Idle),
+            "handle-power-key" => Some(InhibitEvent::HandlePowerKey),
+            "handle-suspend-key" => Some(InhibitEvent::HandleSuspendKey),
+            "handle-hibernate-key" => Some(InhibitEvent::HandleHibernateKey),
+            "handle-lid-switch" => Some(InhibitEvent::HandleLidSwitch),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for InhibitEvent {
@@ -40,8 +74,17 @@ impl fmt::Display for InhibitEvent {
     }
 }
 
-/// A set of events to inhibit.
-#[derive(Default, Eq, PartialEq)]
+impl FromStr for InhibitEvent {
+    type Err = LogindError;
+
+    fn from_str(s: &str) -> Result<InhibitEvent, LogindError> {
+        InhibitEvent::from_name(s)
+            .ok_or_else(|| LogindError::unrecognized_value("inhibit event", s.to_string()))
+    }
+}
+
+/// A set of events to inhibit. This is stored in the colon-delimited format logind expects.
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
 pub struct InhibitEventSet(String);
 
 impl InhibitEventSet {
@@ -52,26 +95,109 @@ impl InhibitEventSet {
 
     /// Creates a new event set containing one event.
     pub fn with_event(event: InhibitEvent) -> InhibitEventSet {
-        InhibitEventSet(format!("{}:", event.as_str()))
+        InhibitEventSet(event.as_str().to_string())
+    }
+
+    /// Creates a new event set containing every event.
+    pub fn all() -> InhibitEventSet {
+        InhibitEvent::ALL.iter().copied().collect()
     }
 
-    /// Add an event to the set. Note that this does not check if `event` is already included.
+    /// Add an event to the set, if it isn't already included.
     pub fn add(&mut self, event: InhibitEvent) -> &mut InhibitEventSet {
-        self.0.push_str(event.as_str());
-        self.0.push(':');
+        if !self.contains(event) {
+            if !self.0.is_empty() {
+                self.0.push(':');
+            }
+            self.0.push_str(event.as_str());
+        }
         self
     }
 
+    /// Remove an event from the set, if it's included.
+    pub fn remove(&mut self, event: InhibitEvent) -> &mut InhibitEventSet {
+        // Rebuild the list rather than splicing the string, so that event names which are
+        // prefixes or suffixes of each other can't be mangled
+        let remaining: Vec<&str> = self
+            .0
+            .split(':')
+            .filter(|name| *name != event.as_str())
+            .collect();
+        self.0 = remaining.join(":");
+        self
+    }
+
+    /// Checks if the set includes `event`.
+    pub fn contains(&self, event: InhibitEvent) -> bool {
+        self.0.split(':').any(|name| name == event.as_str())
+    }
+
+    /// The number of events in the set.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Checks if the set has no events in it.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Parses a colon-delimited list of events, as reported by logind. Unrecognized events are
+    /// skipped.
+    pub(crate) fn parse(events: &str) -> InhibitEventSet {
+        events
+            .split(':')
+            .filter_map(InhibitEvent::from_name)
+            .collect()
+    }
+
+    /// Iterate over the events in the set, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = InhibitEvent> + '_ {
+        self.0.split(':').filter_map(InhibitEvent::from_name)
+    }
+
+    /// The colon-delimited list of events in this set, such as `sleep:idle`.
     pub fn as_str(&self) -> &str {
-        if self.0.is_empty() {
-            ""
-        } else {
-            &self.0[0..self.0.len() - 1] // Drop the trailing `:`
+        &self.0
+    }
+}
+
+impl FromIterator<InhibitEvent> for InhibitEventSet {
+    fn from_iter<I: IntoIterator<Item = InhibitEvent>>(events: I) -> InhibitEventSet {
+        let mut set = InhibitEventSet::new();
+        for event in events {
+            set.add(event);
         }
+        set
+    }
+}
+
+/// Event sets are serialized in logind's colon-delimited format, like `sleep:idle`.
+#[cfg(feature = "serde")]
+impl Serialize for InhibitEventSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for InhibitEventSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<InhibitEventSet, D::Error> {
+        let events = String::deserialize(deserializer)?;
+        events
+            .split(':')
+            .filter(|name| !name.is_empty())
+            .map(|name| name.parse::<InhibitEvent>().map_err(de::Error::custom))
+            .collect()
     }
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum InhibitMode {
     /// An inhibitor lock which prevents the event from occurring.
     Block,
@@ -86,6 +212,14 @@ impl InhibitMode {
             InhibitMode::Delay => "delay",
         }
     }
+
+    pub(crate) fn from_name(name: &str) -> Option<InhibitMode> {
+        match name {
+            "block" => Some(InhibitMode::Block),
+            "delay" => Some(InhibitMode::Delay),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for InhibitMode {
@@ -94,15 +228,93 @@ impl fmt::Display for InhibitMode {
     }
 }
 
+/// Information about an inhibitor lock currently held on the system, as reported by
+/// `Logind::list_inhibitors`.
+#[derive(Debug, Eq, PartialEq)]
+pub struct InhibitorInfo {
+    /// The events inhibited by the lock
+    pub what: InhibitEventSet,
+    /// Human-readable name of the program holding the lock
+    pub who: String,
+    /// Human-readable reason the lock was taken
+    pub why: String,
+    pub mode: InhibitMode,
+    /// UID of the user holding the lock
+    pub uid: u32,
+    /// PID of the process holding the lock
+    pub pid: u32,
+}
+
 /// RAII handle on an inhibitor lock. If this is dropped, the lock is released.
 #[derive(Debug)]
 pub struct InhibitorLock {
     fd: OwnedFd,
+    what: InhibitEventSet,
+    who: String,
+    why: String,
+    mode: InhibitMode,
 }
 
 impl InhibitorLock {
-    pub(crate) fn new(fd: OwnedFd) -> InhibitorLock {
-        InhibitorLock { fd }
+    /// Wraps the file descriptor logind returned for an inhibitor lock. Fails with
+    /// `LogindError::InhibitorFileError` if the descriptor isn't open, so that a bad lock is caught
+    /// when it's taken rather than when it's duplicated or released.
+    pub(crate) fn new(
+        fd: OwnedFd,
+        what: InhibitEventSet,
+        who: String,
+        why: String,
+        mode: InhibitMode,
+    ) -> Result<InhibitorLock, LogindError> {
+        if let Err(err) = fcntl(fd.as_raw_fd(), FcntlArg::F_GETFD) {
+            // Don't try to close a descriptor that isn't open, in case it gets reused
+            fd.into_fd();
+            return Err(LogindError::inhibitor_file_error(
+                "Inhibitor lock file descriptor is invalid".to_string(),
+                err,
+            ));
+        }
+
+        Ok(InhibitorLock {
+            fd,
+            what,
+            who,
+            why,
+            mode,
+        })
+    }
+
+    /// Wraps a file descriptor that's already known to back an inhibitor lock, taking ownership of
+    /// it.
+    fn from_raw_fd(
+        fd: RawFd,
+        what: InhibitEventSet,
+        who: String,
+        why: String,
+        mode: InhibitMode,
+    ) -> Result<InhibitorLock, LogindError> {
+        // Safety: callers pass a freshly-created descriptor that nothing else owns
+        let fd = unsafe { OwnedFd::new(fd) };
+        InhibitorLock::new(fd, what, who, why, mode)
+    }
+
+    /// The events inhibited by this lock
+    pub fn what(&self) -> &InhibitEventSet {
+        &self.what
+    }
+
+    /// Human-readable name of the program that took this lock
+    pub fn who(&self) -> &str {
+        &self.who
+    }
+
+    /// Human-readable reason this lock was taken
+    pub fn why(&self) -> &str {
+        &self.why
+    }
+
+    pub fn mode(&self) -> InhibitMode {
+        self.mode
     }
 
     // Note: OwnedFd's clone() panics on error, so avoid using it here
@@ -118,6 +330,30 @@ impl InhibitorLock {
         })
     }
 
+    /// Creates a second handle on this inhibitor lock, backed by a duplicate file descriptor. The lock
+    /// is held until both handles have been dropped or released.
+    pub fn try_clone(&self) -> Result<InhibitorLock, LogindError> {
+        let fd = self.dup_fd()?;
+        InhibitorLock::from_raw_fd(
+            fd,
+            self.what.clone(),
+            self.who.clone(),
+            self.why.clone(),
+            self.mode,
+        )
+    }
+
+    /// Gives up ownership of the lock without releasing it, returning its file descriptor. This is
+    /// meant for handing the lock to another process, like a child that inherits the descriptor:
+    /// the lock is held until every copy of the descriptor is closed, so it outlives this process
+    /// if the child keeps it open.
+    ///
+    /// This does the same thing as `into_raw_fd`, but makes the intent clear. Use `try_clone`
+    /// first to hand off a copy while keeping the original.
+    pub fn leak(self) -> RawFd {
+        self.fd.into_fd()
+    }
+
     pub fn release(self) -> Result<(), LogindError> {
         unistd::close(self.fd.into_fd()).map_err(|err| {
             LogindError::inhibitor_file_error("Could not release inhibitor lock".to_string(), err)
@@ -125,6 +361,14 @@ impl InhibitorLock {
     }
 }
 
+/// Borrows the file descriptor backing the lock. The lock is still released when the
+/// `InhibitorLock` is dropped, so the file descriptor must not be closed through this.
+impl AsRawFd for InhibitorLock {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
 impl IntoRawFd for InhibitorLock {
     fn into_raw_fd(self) -> RawFd {
         self.fd.into_fd()
@@ -133,6 +377,85 @@ impl IntoRawFd for InhibitorLock {
 
 impl fmt::Display for InhibitorLock {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.fd.as_raw_fd())
+        write!(
+            f,
+            "{} ({} {} for {}: {})",
+            self.fd.as_raw_fd(),
+            self.mode,
+            self.what.as_str(),
+            self.who,
+            self.why
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inhibit_event_round_trips() {
+        let events = [
+            InhibitEvent::Shutdown,
+            InhibitEvent::Sleep,
+            InhibitEvent::Idle,
+            InhibitEvent::HandlePowerKey,
+            InhibitEvent::HandleSuspendKey,
+            InhibitEvent::HandleHibernateKey,
+            InhibitEvent::HandleLidSwitch,
+        ];
+        for &event in events.iter() {
+            assert_eq!(event.as_str().parse::<InhibitEvent>().unwrap(), event);
+            assert_eq!(event.to_string().parse::<InhibitEvent>().unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn event_set_formats_without_trailing_colon() {
+        assert_eq!(InhibitEventSet::new().as_str(), "");
+        assert_eq!(
+            InhibitEventSet::with_event(InhibitEvent::Sleep).as_str(),
+            "sleep"
+        );
+
+        let mut events = InhibitEventSet::with_event(InhibitEvent::Sleep);
+        events.add(InhibitEvent::Idle);
+        assert_eq!(events.as_str(), "sleep:idle");
+
+        events
+            .add(InhibitEvent::Sleep)
+            .add(InhibitEvent::HandleLidSwitch);
+        assert_eq!(events.as_str(), "sleep:idle:handle-lid-switch");
+
+        events.remove(InhibitEvent::Idle);
+        assert_eq!(events.as_str(), "sleep:handle-lid-switch");
+
+        events
+            .remove(InhibitEvent::Sleep)
+            .remove(InhibitEvent::HandleLidSwitch);
+        assert_eq!(events, InhibitEventSet::new());
+    }
+
+    #[test]
+    fn inhibit_event_rejects_unknown() {
+        assert!("handle-lid".parse::<InhibitEvent>().is_err());
+        assert!("".parse::<InhibitEvent>().is_err());
+    }
+
+    #[test]
+    fn lock_rejects_closed_fd() {
+        // Safety: -1 is never a valid descriptor, and `new` doesn't close invalid ones
+        let fd = unsafe { OwnedFd::new(-1) };
+        let result = InhibitorLock::new(
+            fd,
+            InhibitEventSet::with_event(InhibitEvent::Sleep),
+            "test".to_string(),
+            "testing".to_string(),
+            InhibitMode::Block,
+        );
+        assert!(matches!(
+            result,
+            Err(LogindError::InhibitorFileError { .. })
+        ));
     }
 }
@@ -1,22 +1,48 @@
 //! `systemd-logind` client library
-#![feature(backtrace)]
 use std::env;
+use std::sync::mpsc;
 use std::time::Duration;
 
+use dbus::arg::{self, RefArg};
+use dbus::blocking::stdintf::org_freedesktop_dbus::{Properties, PropertiesPropertiesChanged};
 use dbus::blocking::{Connection, Proxy};
 use dbus::Message;
+use log::info;
 
 use crate::api::manager::{
-    OrgFreedesktopLogin1Manager, OrgFreedesktopLogin1ManagerPrepareForSleep,
+    OrgFreedesktopLogin1Manager, OrgFreedesktopLogin1ManagerPrepareForShutdown,
+    OrgFreedesktopLogin1ManagerPrepareForSleep,
 };
-pub use crate::error::LogindError;
-use crate::inhibitor::{InhibitEventSet, InhibitMode, InhibitorLock};
-pub use crate::session::{Session, SessionId};
+pub use crate::callback::{set_error_sink, CallbackError, CallbackResult};
+pub use crate::error::{ErrorBacktrace, LogindError};
+pub use crate::event_loop::{EventLoop, StopHandle};
+pub use crate::events::{Events, LogindEvent};
+pub use crate::idle::IdleWatch;
+use crate::inhibitor::{InhibitEventSet, InhibitMode, InhibitorInfo, InhibitorLock};
+pub use crate::power::{PowerAction, PowerCapability};
+pub use crate::seat::Seat;
+pub use crate::session::{KillWho, Session, SessionId, SessionInfo, SessionProperties};
+pub use crate::signal::SignalToken;
+pub use crate::transport::LogindTransport;
+pub use crate::user::{User, UserInfo, UserState};
 
+// The generated bindings cover the whole logind API, not all of which is wrapped yet
+#[allow(dead_code)]
 mod api;
+mod callback;
+pub mod device;
 mod error;
+mod event_loop;
+mod events;
+mod idle;
 pub mod inhibitor;
+mod power;
+mod properties;
+mod seat;
 mod session;
+mod signal;
+mod transport;
+mod user;
 
 pub fn session_id() -> Result<SessionId, LogindError> {
     match env::var("XDG_SESSION_ID") {
@@ -25,72 +51,400 @@ pub fn session_id() -> Result<SessionId, LogindError> {
     }
 }
 
+static MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+
 /// A logind client connection. This is a relatively thin wrapper over the
 /// [D-Bus API](https://www.freedesktop.org/wiki/Software/systemd/logind/).
-pub struct Logind<'a> {
-    conn: &'a Connection,
+///
+/// Most functionality needs a real `dbus::blocking::Connection`, but the power management methods
+/// work over any `LogindTransport`, so they can be tested without a bus.
+pub struct Logind<'a, T: LogindTransport = Connection> {
+    conn: &'a T,
     timeout: Duration,
+    manager: Proxy<'a, &'a T>,
+    dry_run: bool,
 }
 
-impl<'a> Logind<'a> {
-    pub fn new(conn: &'a Connection) -> Logind {
+impl<'a, T: LogindTransport> Logind<'a, T> {
+    pub fn new(conn: &'a T) -> Logind<'a, T> {
+        let timeout = Duration::from_millis(500);
         Logind {
             conn,
-            timeout: Duration::from_millis(500),
+            timeout,
+            manager: Proxy::new(
+                "org.freedesktop.login1",
+                "/org/freedesktop/login1",
+                timeout,
+                conn,
+            ),
+            dry_run: false,
         }
     }
 
-    /// Get a handle to a logind session by ID.
+    /// A `Logind` for signal callbacks, which inherits the dry-run setting of the `Logind` (or
+    /// `Session`) the callback was registered on.
+    pub(crate) fn with_dry_run(conn: &'a T, dry_run: bool) -> Logind<'a, T> {
+        let mut logind = Logind::new(conn);
+        logind.dry_run = dry_run;
+        logind
+    }
+
+    /// In dry-run mode, the power methods (`power`, `suspend`, `reboot`, and so on) only log what
+    /// they would have done, without calling logind. This is off by default. Sessions and signal
+    /// callbacks created from this `Logind` inherit the setting.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Attempt to perform a power action, such as suspending or rebooting the system. If
+    /// `interactive`, PolicyKit may prompt the current user for authentication if needed.
+    pub fn power(&self, action: PowerAction, interactive: bool) -> Result<(), LogindError> {
+        if self.dry_run {
+            info!(
+                "Dry run: not performing {} (interactive: {})",
+                action, interactive
+            );
+            return Ok(());
+        }
+        self.manager().method_call::<(), _, _, _>(
+            MANAGER_INTERFACE,
+            action.method(),
+            (interactive,),
+        )?;
+        Ok(())
+    }
+
+    /// Attempt to suspend the system. If `interactive`, PolicyKit may prompt the current user
+    /// for authentication if needed.
+    pub fn suspend(&self, interactive: bool) -> Result<(), LogindError> {
+        self.power(PowerAction::Suspend, interactive)
+    }
+
+    /// Attempt to reboot the system. If `interactive`, PolicyKit may prompt the current user for
+    /// authentication.
+    pub fn reboot(&self, interactive: bool) -> Result<(), LogindError> {
+        self.power(PowerAction::Reboot, interactive)
+    }
+
+    /// Attempt to power off the system. If `interactive`, PolicyKit may prompt the current user for
+    /// authentication.
+    pub fn power_off(&self, interactive: bool) -> Result<(), LogindError> {
+        self.power(PowerAction::PowerOff, interactive)
+    }
+
+    /// Attempt to hibernate the system. If `interactive`, PolicyKit may prompt the current user for
+    /// authentication.
+    pub fn hibernate(&self, interactive: bool) -> Result<(), LogindError> {
+        self.power(PowerAction::Hibernate, interactive)
+    }
+
+    /// Attempt to both suspend and hibernate the system, so that it resumes from RAM if possible
+    /// but survives losing power. If `interactive`, PolicyKit may prompt the current user for
+    /// authentication.
+    pub fn hybrid_sleep(&self, interactive: bool) -> Result<(), LogindError> {
+        self.power(PowerAction::HybridSleep, interactive)
+    }
+
+    /// Attempt to suspend the system, hibernating it after a delay configured in `logind.conf`. If
+    /// `interactive`, PolicyKit may prompt the current user for authentication.
+    pub fn suspend_then_hibernate(&self, interactive: bool) -> Result<(), LogindError> {
+        self.power(PowerAction::SuspendThenHibernate, interactive)
+    }
+
+    /// Attempt to halt the system, stopping it without powering off. If `interactive`, PolicyKit
+    /// may prompt the current user for authentication.
+    pub fn halt(&self, interactive: bool) -> Result<(), LogindError> {
+        self.power(PowerAction::Halt, interactive)
+    }
+
+    /// Checks if a power action can be performed with [`power`](Logind::power).
+    pub fn can_power(&self, action: PowerAction) -> Result<PowerCapability, LogindError> {
+        self.can(action.can_method())
+    }
+
+    /// Checks if the system can be suspended.
+    pub fn can_suspend(&self) -> Result<PowerCapability, LogindError> {
+        self.can_power(PowerAction::Suspend)
+    }
+
+    /// Checks if the system can be rebooted.
+    pub fn can_reboot(&self) -> Result<PowerCapability, LogindError> {
+        self.can_power(PowerAction::Reboot)
+    }
+
+    /// Checks if the system can be powered off.
+    pub fn can_power_off(&self) -> Result<PowerCapability, LogindError> {
+        self.can_power(PowerAction::PowerOff)
+    }
+
+    /// Checks if the system can be hibernated.
+    pub fn can_hibernate(&self) -> Result<PowerCapability, LogindError> {
+        self.can_power(PowerAction::Hibernate)
+    }
+
+    /// Checks if the system can be put into hybrid sleep.
+    pub fn can_hybrid_sleep(&self) -> Result<PowerCapability, LogindError> {
+        self.can_power(PowerAction::HybridSleep)
+    }
+
+    /// Checks if the system can be suspended and then hibernated.
+    pub fn can_suspend_then_hibernate(&self) -> Result<PowerCapability, LogindError> {
+        self.can_power(PowerAction::SuspendThenHibernate)
+    }
+
+    /// Checks if the system can be halted.
+    pub fn can_halt(&self) -> Result<PowerCapability, LogindError> {
+        self.can_power(PowerAction::Halt)
+    }
+
+    /// Checks if the system can be rebooted into the firmware setup interface.
+    pub fn can_reboot_to_firmware_setup(&self) -> Result<PowerCapability, LogindError> {
+        self.can("CanRebootToFirmwareSetup")
+    }
+
+    fn can(&self, method: &str) -> Result<PowerCapability, LogindError> {
+        let (result,): (String,) = self.manager().method_call(MANAGER_INTERFACE, method, ())?;
+        Ok(PowerCapability::from_result(&result))
+    }
+
+    fn manager(&self) -> &Proxy<'a, &'a T> {
+        &self.manager
+    }
+}
+
+impl<'a> Logind<'a> {
+    /// Get a handle to a logind session by ID. Passing [`SessionId::current()`] gets the caller's
+    /// own session.
     pub fn session(&self, id: &SessionId) -> Result<Session<'a>, LogindError> {
+        let path = self.session_path(id)?;
+        let proxy = Proxy::new(
+            "org.freedesktop.login1",
+            path,
+            self.timeout,
+            self.conn,
+        );
+        Ok(Session::new(proxy, self.dry_run))
+    }
+
+    /// The D-Bus object path of a session, for use with lower-level D-Bus calls.
+    pub fn session_path(&self, id: &SessionId) -> Result<dbus::Path<'static>, LogindError> {
         let manager = self.manager();
-        let path = manager.get_session(id.as_str())?;
+        Ok(manager.get_session(id.as_str())?)
+    }
+
+    /// UID of the user owning a session. Like [`session`](Logind::session), this accepts
+    /// [`SessionId::current()`].
+    pub fn uid_of_session(&self, id: &SessionId) -> Result<u32, LogindError> {
+        self.session(id)?.uid()
+    }
+
+    /// Get a handle to the seat a session is on, or `None` if it has no seat. Like
+    /// [`session`](Logind::session), this accepts [`SessionId::current()`].
+    pub fn seat_of_session(&self, id: &SessionId) -> Result<Option<Seat<'a>>, LogindError> {
+        match self.session(id)?.seat()? {
+            Some(seat) => Ok(Some(self.seat(&seat)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get a handle to a logind seat by ID, such as `seat0`.
+    pub fn seat(&self, id: &str) -> Result<Seat<'a>, LogindError> {
+        let manager = self.manager();
+        let path = manager.get_seat(id)?;
         let proxy = Proxy::new(
             "org.freedesktop.login1",
             path,
             self.timeout,
             self.conn,
         );
-        Ok(Session::new(proxy))
+        Ok(Seat::new(proxy))
     }
 
-    /// Get a handle to the current logind session.
+    /// Get a handle to a logind user by UID.
+    pub fn user(&self, uid: u32) -> Result<User<'a>, LogindError> {
+        let manager = self.manager();
+        let path = manager.get_user(uid)?;
+        let proxy = Proxy::new(
+            "org.freedesktop.login1",
+            path,
+            self.timeout,
+            self.conn,
+        );
+        Ok(User::new(proxy))
+    }
+
+    /// Get a handle to the session a process belongs to. A PID of 0 means the calling process.
+    pub fn get_session_by_pid(&self, pid: u32) -> Result<Session<'a>, LogindError> {
+        let manager = self.manager();
+        let path = manager.get_session_by_pid(pid)?;
+        let proxy = Proxy::new(
+            "org.freedesktop.login1",
+            path,
+            self.timeout,
+            self.conn,
+        );
+        Ok(Session::new(proxy, self.dry_run))
+    }
+
+    /// Get a handle to the current logind session. This uses `XDG_SESSION_ID` if it's set, and
+    /// otherwise asks logind which session this process belongs to.
     pub fn current_session(&self) -> Result<Session<'a>, LogindError> {
-        let id = session_id()?;
-        self.session(&id)
+        match session_id() {
+            Ok(id) => self.session(&id),
+            Err(_) => match self.get_session_by_pid(0) {
+                Err(e) if e.is_no_such_session() => Err(LogindError::no_session_id()),
+                result => result,
+            },
+        }
     }
 
-    /// Attempt to suspend the system. If `interactive`, PolicyKit may prompt the current user
-    /// for authentication if needed.
-    pub fn suspend(&self, interactive: bool) -> Result<(), LogindError> {
+    /// List all sessions currently known to logind.
+    pub fn list_sessions(&self) -> Result<Vec<SessionInfo>, LogindError> {
+        let manager = self.manager();
+        Ok(manager
+            .list_sessions()?
+            .into_iter()
+            .map(|(id, uid, user, seat, _)| SessionInfo {
+                id: SessionId::new(id),
+                uid,
+                user,
+                seat,
+            })
+            .collect())
+    }
+
+    /// List all users currently known to logind, including lingering users.
+    pub fn list_users(&self) -> Result<Vec<UserInfo>, LogindError> {
+        let manager = self.manager();
+        Ok(manager
+            .list_users()?
+            .into_iter()
+            .map(|(uid, name, _)| UserInfo { uid, name })
+            .collect())
+    }
+
+    /// List the IDs of all seats, such as `seat0`.
+    pub fn list_seats(&self) -> Result<Vec<String>, LogindError> {
+        let manager = self.manager();
+        Ok(manager
+            .list_seats()?
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect())
+    }
+
+    /// Terminate a session by ID, killing all of its processes. This saves looking up the session
+    /// first with [`session`](Logind::session).
+    pub fn terminate_session(&self, id: &SessionId) -> Result<(), LogindError> {
         let manager = self.manager();
-        manager.suspend(interactive)?;
+        manager.terminate_session(id.as_str())?;
         Ok(())
     }
 
-    /// Attempt to reboot the system. If `interactive`, PolicyKit may prompt the current user for
-    /// authentication.
-    pub fn reboot(&self, interactive: bool) -> Result<(), LogindError> {
+    /// Send a signal to the leader or all processes of a session, by ID.
+    pub fn kill_session(
+        &self,
+        id: &SessionId,
+        who: KillWho,
+        signal: i32,
+    ) -> Result<(), LogindError> {
         let manager = self.manager();
-        manager.reboot(interactive)?;
+        manager.kill_session(id.as_str(), who.as_str(), signal)?;
         Ok(())
     }
 
-    /// Attempt to power off the system. If `interactive`, PolicyKit may prompt the current user for
-    /// authentication.
-    pub fn power_off(&self, interactive: bool) -> Result<(), LogindError> {
+    /// Terminate all sessions on a seat, such as `seat0`. Fails with `LogindError::NoSuchSeat` if
+    /// the seat doesn't exist.
+    pub fn terminate_seat(&self, id: &str) -> Result<(), LogindError> {
         let manager = self.manager();
-        manager.power_off(interactive)?;
+        manager.terminate_seat(id)?;
         Ok(())
     }
 
-    /// Attempt to hibernate the system. If `interactive`, PolicyKit may prompt the current user for
+    /// Terminate all of a user's sessions and processes. Fails with `LogindError::NoSuchUser` if
+    /// the user isn't logged in.
+    pub fn terminate_user(&self, uid: u32) -> Result<(), LogindError> {
+        let manager = self.manager();
+        manager.terminate_user(uid)?;
+        Ok(())
+    }
+
+    /// Send `signal`, such as `SIGKILL` or `SIGHUP`, to all of a user's processes. Unlike
+    /// [`terminate_user`](Logind::terminate_user), this doesn't end the user's sessions itself;
+    /// what happens depends on how the processes handle the signal. Fails with
+    /// `LogindError::NoSuchUser` if the user isn't logged in.
+    ///
+    /// This needs the `org.freedesktop.login1.manage` PolicyKit action, which is normally only
+    /// granted to root, and there's no interactive authentication. Otherwise, it fails with
+    /// `LogindError::NotAuthorized`.
+    pub fn kill_user(&self, uid: u32, signal: i32) -> Result<(), LogindError> {
+        let manager = self.manager();
+        manager.kill_user(uid, signal)?;
+        Ok(())
+    }
+
+    /// Enable or disable lingering for a user. While lingering, the user's service manager keeps
+    /// running after they log out. If `interactive`, PolicyKit may prompt the current user for
     /// authentication.
-    pub fn hibernate(&self, interactive: bool) -> Result<(), LogindError> {
+    pub fn set_user_linger(
+        &self,
+        uid: u32,
+        enable: bool,
+        interactive: bool,
+    ) -> Result<(), LogindError> {
+        let manager = self.manager();
+        manager.set_user_linger(uid, enable, interactive)?;
+        Ok(())
+    }
+
+    /// Permanently attach a device to a seat, such as `seat1`, creating the seat if needed.
+    /// `sysfs_path` is the device's path under `/sys`. If `interactive`, PolicyKit may prompt the
+    /// current user for authentication. Fails with `LogindError::NotAuthorized` if the user isn't
+    /// allowed to change seat assignments.
+    pub fn attach_device(
+        &self,
+        seat: &str,
+        sysfs_path: &str,
+        interactive: bool,
+    ) -> Result<(), LogindError> {
         let manager = self.manager();
-        manager.hibernate(interactive)?;
+        manager.attach_device(seat, sysfs_path, interactive)?;
         Ok(())
     }
 
+    /// Remove all device assignments made with [`attach_device`](Logind::attach_device), so every
+    /// device goes back to `seat0`. Like `attach_device`, this fails with
+    /// `LogindError::NotAuthorized` if the user isn't allowed to change seat assignments.
+    pub fn flush_devices(&self, interactive: bool) -> Result<(), LogindError> {
+        let manager = self.manager();
+        manager.flush_devices(interactive)?;
+        Ok(())
+    }
+
+    /// Set the message broadcast to logged-in users before a scheduled shutdown, and whether to
+    /// broadcast one at all. An empty message with `enable` set to `false` disables wall messages.
+    pub fn set_wall_message(&self, message: &str, enable: bool) -> Result<(), LogindError> {
+        let manager = self.manager();
+        manager.set_wall_message_(message, enable)?;
+        Ok(())
+    }
+
+    /// The message broadcast to logged-in users before a scheduled shutdown.
+    pub fn wall_message(&self) -> Result<String, LogindError> {
+        let manager = self.manager();
+        Ok(manager.wall_message()?)
+    }
+
+    /// Take an inhibitor lock on `events`, which is held until the returned `InhibitorLock` is
+    /// released or dropped. `who` and `why` are shown to users, such as by
+    /// `systemd-inhibit --list`.
+    ///
+    /// Fails with `LogindError::EmptyEventSet` if `events` is empty, since such a lock wouldn't
+    /// inhibit anything.
     pub fn inhibit(
         &self,
         who: &str,
@@ -98,41 +452,293 @@ impl<'a> Logind<'a> {
         events: &InhibitEventSet,
         mode: InhibitMode,
     ) -> Result<InhibitorLock, LogindError> {
+        if events.is_empty() {
+            return Err(LogindError::empty_event_set());
+        }
         let manager = self.manager();
         let fd = manager.inhibit(events.as_str(), who, why, mode.as_str())?;
-        Ok(InhibitorLock::new(fd))
+        InhibitorLock::new(fd, events.clone(), who.to_string(), why.to_string(), mode)
     }
 
-    pub fn on_sleep<F: Fn(Logind) + Send + 'static, G: Fn(Logind) + Send + 'static>(
+    /// Run `f` while holding an inhibitor lock, such as to keep the system from sleeping during a
+    /// backup. The lock is released once `f` returns, or if it panics.
+    ///
+    /// If releasing the lock fails, the error is returned and `f`'s result is discarded.
+    pub fn inhibit_for<R, F: FnOnce() -> R>(
+        &self,
+        who: &str,
+        why: &str,
+        events: &InhibitEventSet,
+        mode: InhibitMode,
+        f: F,
+    ) -> Result<R, LogindError> {
+        // If `f` panics, dropping the lock while unwinding releases it
+        let lock = self.inhibit(who, why, events, mode)?;
+        let result = f();
+        lock.release()?;
+        Ok(result)
+    }
+
+    /// List all inhibitor locks currently held on the system.
+    pub fn list_inhibitors(&self) -> Result<Vec<InhibitorInfo>, LogindError> {
+        let manager = self.manager();
+        manager
+            .list_inhibitors()?
+            .into_iter()
+            .map(|(what, who, why, mode, uid, pid)| {
+                let mode = InhibitMode::from_name(&mode)
+                    .ok_or_else(|| LogindError::unrecognized_value("inhibitor mode", mode))?;
+                Ok(InhibitorInfo {
+                    what: InhibitEventSet::parse(&what),
+                    who,
+                    why,
+                    mode,
+                    uid,
+                    pid,
+                })
+            })
+            .collect()
+    }
+
+    /// Events which are currently blocked by an inhibitor lock.
+    pub fn block_inhibited(&self) -> Result<InhibitEventSet, LogindError> {
+        let manager = self.manager();
+        Ok(InhibitEventSet::parse(&manager.block_inhibited()?))
+    }
+
+    /// Events which are currently delayed by an inhibitor lock.
+    pub fn delay_inhibited(&self) -> Result<InhibitEventSet, LogindError> {
+        let manager = self.manager();
+        Ok(InhibitEventSet::parse(&manager.delay_inhibited()?))
+    }
+
+    /// Checks if the system is about to go to sleep. This is the state most recently announced by
+    /// the `PrepareForSleep` signal.
+    pub fn preparing_for_sleep(&self) -> Result<bool, LogindError> {
+        let manager = self.manager();
+        Ok(manager.preparing_for_sleep()?)
+    }
+
+    /// Checks if the system is about to shut down. This is the state most recently announced by
+    /// the `PrepareForShutdown` signal.
+    pub fn preparing_for_shutdown(&self) -> Result<bool, LogindError> {
+        let manager = self.manager();
+        Ok(manager.preparing_for_shutdown()?)
+    }
+
+    /// Request that the next boot go into the firmware setup interface (such as the UEFI setup
+    /// screen), or cancel that request. This takes effect on the next reboot, such as from
+    /// [`reboot`](Logind::reboot). Fails with `LogindError::NotSupported` if the firmware doesn't
+    /// support it.
+    pub fn set_reboot_to_firmware_setup(&self, enable: bool) -> Result<(), LogindError> {
+        let manager = self.manager();
+        manager.set_reboot_to_firmware_setup_(enable)?;
+        Ok(())
+    }
+
+    /// Checks if the next boot will go into the firmware setup interface.
+    pub fn reboot_to_firmware_setup(&self) -> Result<bool, LogindError> {
+        let manager = self.manager();
+        Ok(manager.reboot_to_firmware_setup()?)
+    }
+
+    /// Checks if the laptop lid is closed. logind doesn't send `PropertiesChanged` for this, so
+    /// watching for the lid closing means polling it.
+    pub fn lid_closed(&self) -> Result<bool, LogindError> {
+        let manager = self.manager();
+        Ok(manager.lid_closed()?)
+    }
+
+    /// Checks if the system is docked, or has more than one display connected.
+    pub fn docked(&self) -> Result<bool, LogindError> {
+        let manager = self.manager();
+        Ok(manager.docked()?)
+    }
+
+    /// Checks if the system is running on AC power rather than battery.
+    pub fn on_external_power(&self) -> Result<bool, LogindError> {
+        let manager = self.manager();
+        Ok(manager.on_external_power()?)
+    }
+
+    /// Register callbacks to run before the system goes to sleep and after it resumes. The callbacks
+    /// are unregistered when the returned token is dropped. Errors returned by the callbacks are
+    /// passed to the error sink (see [`set_error_sink`]).
+    pub fn on_sleep<F, G>(
         &self,
         pre_sleep: F,
         post_sleep: G,
-    ) -> Result<(), LogindError> {
+    ) -> Result<SignalToken<'a>, LogindError>
+    where
+        F: Fn(Logind) -> CallbackResult + Send + 'static,
+        G: Fn(Logind) -> CallbackResult + Send + 'static,
+    {
+        let dry_run = self.dry_run;
         let manager = self.manager();
         match manager.match_signal(
             move |signal: OrgFreedesktopLogin1ManagerPrepareForSleep,
                   conn: &Connection,
                   _: &Message| {
+                // TODO: would be nice to make `Logind` less tied to dbus' threading model where we have to keep making new instances
+                let logind = Logind::with_dry_run(conn, dry_run);
                 if signal.arg0 {
-                    // TODO: would be nice to make `Logind` less tied to dbus' threading model where we have to keep making new instances
-                    pre_sleep(Logind::new(conn));
+                    callback::report("PrepareForSleep", pre_sleep(logind));
                 } else {
-                    post_sleep(Logind::new(conn));
+                    callback::report("PrepareForSleep", post_sleep(logind));
                 }
                 true
             },
         ) {
-            Ok(_) => Ok(()),
+            Ok(token) => Ok(SignalToken::new(self.conn, token)),
             Err(e) => Err(LogindError::match_failed("PrepareForSleep", e)),
         }
     }
 
-    fn manager(&self) -> Proxy<'_, &'a Connection> {
-        Proxy::new(
-            "org.freedesktop.login1",
-            "/org/freedesktop/login1",
-            self.timeout,
-            self.conn,
-        )
+    /// Register a callback to run when the system is about to shut down or reboot. The callback
+    /// receives `true` when the shutdown starts, and `false` if it's cancelled afterwards. Errors
+    /// returned by the callback are passed to the error sink.
+    pub fn on_shutdown<F: Fn(Logind, bool) -> CallbackResult + Send + 'static>(
+        &self,
+        cb: F,
+    ) -> Result<SignalToken<'a>, LogindError> {
+        let dry_run = self.dry_run;
+        let manager = self.manager();
+        match manager.match_signal(
+            move |signal: OrgFreedesktopLogin1ManagerPrepareForShutdown,
+                  conn: &Connection,
+                  _: &Message| {
+                let logind = Logind::with_dry_run(conn, dry_run);
+                callback::report("PrepareForShutdown", cb(logind, signal.arg0));
+                true
+            },
+        ) {
+            Ok(token) => Ok(SignalToken::new(self.conn, token)),
+            Err(e) => Err(LogindError::match_failed("PrepareForShutdown", e)),
+        }
+    }
+
+    /// Register a callback to run when a property of the logind manager changes, such as
+    /// `LidClosed` on `org.freedesktop.login1.Manager`. The callback receives the new value. If
+    /// logind only reports that the property was invalidated, its value is read back first. Errors
+    /// returned by the callback are passed to the error sink.
+    pub fn on_property_changed<F>(
+        &self,
+        interface: &str,
+        property: &str,
+        cb: F,
+    ) -> Result<SignalToken<'a>, LogindError>
+    where
+        F: Fn(Logind, &(dyn RefArg + 'static)) -> CallbackResult + Send + 'static,
+    {
+        let interface = interface.to_string();
+        let property = property.to_string();
+        let dry_run = self.dry_run;
+        let manager = self.manager();
+        match manager.match_signal(
+            move |signal: PropertiesPropertiesChanged, conn: &Connection, _: &Message| {
+                let logind = Logind::with_dry_run(conn, dry_run);
+                match changed_value(&signal, &logind, &interface, &property) {
+                    Ok(Some(value)) => callback::report("PropertiesChanged", cb(logind, &*value)),
+                    Ok(None) => (),
+                    Err(e) => callback::report("PropertiesChanged", Err(e.into())),
+                }
+                true
+            },
+        ) {
+            Ok(token) => Ok(SignalToken::new(self.conn, token)),
+            Err(e) => Err(LogindError::match_failed("PropertiesChanged", e)),
+        }
+    }
+
+    /// Like [`on_property_changed`](Logind::on_property_changed), for boolean properties. Changes
+    /// to a value of any other type are reported to the error sink.
+    pub fn on_bool_property_changed<F>(
+        &self,
+        interface: &str,
+        property: &'static str,
+        cb: F,
+    ) -> Result<SignalToken<'a>, LogindError>
+    where
+        F: Fn(Logind, bool) -> CallbackResult + Send + 'static,
+    {
+        self.on_property_changed(interface, property, move |logind, value| {
+            match arg::cast::<bool>(value) {
+                Some(&value) => cb(logind, value),
+                None => Err(LogindError::invalid_property(property).into()),
+            }
+        })
+    }
+
+    /// Subscribe to sleep, shutdown, and current session lock/unlock signals as a single stream of
+    /// [`LogindEvent`]s. The subscriptions are removed when the returned iterator is dropped.
+    pub fn events(&self) -> Result<Events<'a>, LogindError> {
+        let (sender, receiver) = mpsc::channel();
+        // If the iterator is gone, nobody is listening, so send failures can be ignored
+        let send = |event: LogindEvent| {
+            let sender = sender.clone();
+            move |_: Logind| {
+                let _ = sender.send(event);
+                Ok(())
+            }
+        };
+
+        let session = self.current_session()?;
+        let subscriptions = vec![
+            self.on_sleep(send(LogindEvent::SleepStart), send(LogindEvent::SleepEnd))?,
+            session.on_lock(send(LogindEvent::SessionLocked))?,
+            session.on_unlock(send(LogindEvent::SessionUnlocked))?,
+            self.on_shutdown({
+                let sender = sender.clone();
+                move |_, shutting_down| {
+                    let event = if shutting_down {
+                        LogindEvent::ShutdownScheduled
+                    } else {
+                        LogindEvent::ShutdownCancelled
+                    };
+                    let _ = sender.send(event);
+                    Ok(())
+                }
+            })?,
+        ];
+        Ok(Events::new(self.conn, receiver, subscriptions))
+    }
+
+    /// Watch a session's idle hint, running `cb` once the session has been idle for at least
+    /// `threshold`. This works without a display server, since it only relies on whatever sets the
+    /// session's idle hint. The returned [`IdleWatch`] has to be polled; see its docs for details.
+    pub fn watch_idle<F: FnMut(Duration) -> CallbackResult + 'a>(
+        &self,
+        session: &SessionId,
+        threshold: Duration,
+        cb: F,
+    ) -> Result<IdleWatch<'a>, LogindError> {
+        Ok(IdleWatch::new(self.session(session)?, threshold, cb))
+    }
+
+    /// Create an event loop to run signal callbacks registered on this connection.
+    pub fn event_loop(&self) -> EventLoop<'a> {
+        EventLoop::new(self.conn)
+    }
+}
+
+/// The new value of a property, if a `PropertiesChanged` signal says it changed.
+fn changed_value(
+    signal: &PropertiesPropertiesChanged,
+    logind: &Logind,
+    interface: &str,
+    property: &str,
+) -> Result<Option<Box<dyn RefArg>>, LogindError> {
+    if signal.interface_name != interface {
+        return Ok(None);
+    }
+    if let Some(value) = signal.changed_properties.get(property) {
+        return Ok(Some(value.0.box_clone()));
+    }
+    // Invalidated properties have to be read back
+    let invalidated = &signal.invalidated_properties;
+    if invalidated.iter().any(|name| name == property) {
+        let value = logind.manager().get(interface, property)?;
+        return Ok(Some(value));
     }
+    Ok(None)
 }
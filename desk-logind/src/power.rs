@@ -0,0 +1,143 @@
+//! Power management support
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::LogindError;
+
+/// A system power operation, which can be performed with `Logind::power`
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub enum PowerAction {
+    Suspend,
+    Hibernate,
+    /// Suspend, with a hibernation image written in case power is lost
+    HybridSleep,
+    /// Suspend, then hibernate after a delay configured in `logind.conf`
+    SuspendThenHibernate,
+    Reboot,
+    PowerOff,
+    /// Stop the system without powering it off
+    Halt,
+}
+
+impl PowerAction {
+    /// All power actions
+    pub const ALL: [PowerAction; 7] = [
+        PowerAction::Suspend,
+        PowerAction::Hibernate,
+        PowerAction::HybridSleep,
+        PowerAction::SuspendThenHibernate,
+        PowerAction::Reboot,
+        PowerAction::PowerOff,
+        PowerAction::Halt,
+    ];
+
+    /// The action's name, matching the corresponding `systemctl` command, such as `poweroff`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PowerAction::Suspend => "suspend",
+            PowerAction::Hibernate => "hibernate",
+            PowerAction::HybridSleep => "hybrid-sleep",
+            PowerAction::SuspendThenHibernate => "suspend-then-hibernate",
+            PowerAction::Reboot => "reboot",
+            PowerAction::PowerOff => "poweroff",
+            PowerAction::Halt => "halt",
+        }
+    }
+
+    /// Name of the logind manager method that performs this action
+    pub(crate) fn method(self) -> &'static str {
+        match self {
+            PowerAction::Suspend => "Suspend",
+            PowerAction::Hibernate => "Hibernate",
+            PowerAction::HybridSleep => "HybridSleep",
+            PowerAction::SuspendThenHibernate => "SuspendThenHibernate",
+            PowerAction::Reboot => "Reboot",
+            PowerAction::PowerOff => "PowerOff",
+            PowerAction::Halt => "Halt",
+        }
+    }
+
+    /// Name of the logind manager method that checks if this action is available
+    pub(crate) fn can_method(self) -> &'static str {
+        match self {
+            PowerAction::Suspend => "CanSuspend",
+            PowerAction::Hibernate => "CanHibernate",
+            PowerAction::HybridSleep => "CanHybridSleep",
+            PowerAction::SuspendThenHibernate => "CanSuspendThenHibernate",
+            PowerAction::Reboot => "CanReboot",
+            PowerAction::PowerOff => "CanPowerOff",
+            PowerAction::Halt => "CanHalt",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<PowerAction> {
+        PowerAction::ALL
+            .iter()
+            .copied()
+            .find(|action| action.as_str() == name)
+    }
+}
+
+impl fmt::Display for PowerAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PowerAction {
+    type Err = LogindError;
+
+    fn from_str(s: &str) -> Result<PowerAction, LogindError> {
+        PowerAction::from_name(s)
+            .ok_or_else(|| LogindError::unrecognized_value("power action", s.to_string()))
+    }
+}
+
+/// Whether a power operation like suspending or rebooting is available, as reported by logind's
+/// `Can*` methods
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub enum PowerCapability {
+    /// The operation is supported and the current user may perform it
+    Yes,
+    /// The operation is supported, but the current user must authenticate first
+    Challenge,
+    /// The operation is supported, but the current user may not perform it
+    No,
+    /// The operation is not supported on this system, for example hibernating without swap
+    Na,
+    /// A capability this library doesn't know about
+    Other(String),
+}
+
+impl PowerCapability {
+    pub(crate) fn from_result(result: &str) -> PowerCapability {
+        match result {
+            "yes" => PowerCapability::Yes,
+            "challenge" => PowerCapability::Challenge,
+            "no" => PowerCapability::No,
+            "na" => PowerCapability::Na,
+            other => PowerCapability::Other(other.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            PowerCapability::Yes => "yes",
+            PowerCapability::Challenge => "challenge",
+            PowerCapability::No => "no",
+            PowerCapability::Na => "na",
+            PowerCapability::Other(result) => result,
+        }
+    }
+
+    /// Checks if the operation can be attempted, possibly after authenticating.
+    pub fn is_available(&self) -> bool {
+        !matches!(self, PowerCapability::No | PowerCapability::Na)
+    }
+}
+
+impl fmt::Display for PowerCapability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,27 @@
+//! Helpers for decoding the property maps returned by `org.freedesktop.DBus.Properties.GetAll`
+use dbus::arg::{prop_cast, PropMap, RefArg};
+
+use crate::error::LogindError;
+
+/// Reads a property with a plain D-Bus type (string, boolean, integer, etc.) out of `props`.
+pub(crate) fn get<T: Clone + 'static>(
+    props: &PropMap,
+    property: &'static str,
+) -> Result<T, LogindError> {
+    prop_cast::<T>(props, property)
+        .cloned()
+        .ok_or_else(|| LogindError::invalid_property(property))
+}
+
+/// Reads the first field of a struct-valued property, such as the `(so)` pairs logind uses to
+/// refer to other objects.
+pub(crate) fn first_field<'a>(
+    props: &'a PropMap,
+    property: &'static str,
+) -> Result<&'a dyn RefArg, LogindError> {
+    props
+        .get(property)
+        .and_then(|value| value.0.as_iter())
+        .and_then(|mut fields| fields.next())
+        .ok_or_else(|| LogindError::invalid_property(property))
+}
@@ -0,0 +1,73 @@
+use dbus::blocking::{Connection, Proxy};
+use dbus::Path;
+
+use crate::api::seat::OrgFreedesktopLogin1Seat;
+use crate::error::LogindError;
+use crate::session::SessionId;
+
+/// Handle to a logind seat
+pub struct Seat<'a> {
+    proxy: Proxy<'a, &'a Connection>,
+}
+
+impl<'a> Seat<'a> {
+    pub(crate) fn new(proxy: Proxy<'a, &'a Connection>) -> Seat<'a> {
+        Seat { proxy }
+    }
+
+    pub fn id(&self) -> Result<String, LogindError> {
+        let id = self.proxy.id()?;
+        Ok(id)
+    }
+
+    /// Whether this seat supports running multiple sessions at once (i.e. has VTs to switch
+    /// between).
+    pub fn can_multi_session(&self) -> Result<bool, LogindError> {
+        Ok(self.proxy.can_multi_session()?)
+    }
+
+    /// Whether this seat has a text console.
+    pub fn can_tty(&self) -> Result<bool, LogindError> {
+        Ok(self.proxy.can_tty()?)
+    }
+
+    /// Whether this seat has a graphics device.
+    pub fn can_graphical(&self) -> Result<bool, LogindError> {
+        Ok(self.proxy.can_graphical()?)
+    }
+
+    /// Gets the ID and object path of the session currently active on this seat, or `None` if no
+    /// session is active.
+    pub fn active_session(&self) -> Result<Option<(SessionId, Path<'static>)>, LogindError> {
+        let (id, path) = self.proxy.active_session()?;
+        if id.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some((SessionId::new(id), path)))
+        }
+    }
+
+    /// Activate a session on this seat, bringing it to the foreground.
+    pub fn activate_session(&self, id: &SessionId) -> Result<(), LogindError> {
+        self.proxy.activate_session(id.as_str())?;
+        Ok(())
+    }
+
+    /// Switch to the given virtual terminal.
+    pub fn switch_to(&self, vtnr: u32) -> Result<(), LogindError> {
+        self.proxy.switch_to(vtnr)?;
+        Ok(())
+    }
+
+    /// Switch to the next virtual terminal.
+    pub fn switch_to_next(&self) -> Result<(), LogindError> {
+        self.proxy.switch_to_next()?;
+        Ok(())
+    }
+
+    /// Switch to the previous virtual terminal.
+    pub fn switch_to_previous(&self) -> Result<(), LogindError> {
+        self.proxy.switch_to_previous()?;
+        Ok(())
+    }
+}
@@ -1,13 +1,28 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::os::unix::io::RawFd;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
 use dbus::blocking::{Connection, Proxy};
 use dbus::Message;
 
 use crate::api::session::{
-    OrgFreedesktopLogin1Session, OrgFreedesktopLogin1SessionLock, OrgFreedesktopLogin1SessionUnlock,
+    OrgFreedesktopLogin1Session, OrgFreedesktopLogin1SessionLock,
+    OrgFreedesktopLogin1SessionPauseDevice, OrgFreedesktopLogin1SessionResumeDevice,
+    OrgFreedesktopLogin1SessionUnlock,
 };
+use crate::callback::{self, CallbackResult};
+use crate::device::{DeviceFd, PauseKind};
 use crate::error::LogindError;
+use crate::properties;
+use crate::signal::SignalToken;
 use crate::Logind;
 
-#[derive(Debug, Eq, PartialEq, Hash)]
+static SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct SessionId(String);
 
 impl SessionId {
@@ -15,74 +30,459 @@ impl SessionId {
         SessionId(s)
     }
 
+    /// The calling process's session. logind resolves the empty session ID to whichever session
+    /// the caller belongs to, so this works even if `XDG_SESSION_ID` isn't set. It can't be used
+    /// for anything besides looking up the session, such as with [`Logind::session`].
+    pub fn current() -> SessionId {
+        SessionId(String::new())
+    }
+
     pub fn as_str(&self) -> &str {
         &self.0
     }
 }
 
-/// Handle to a logind session
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for SessionId {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<SessionId, Infallible> {
+        Ok(SessionId(s.to_string()))
+    }
+}
+
+impl AsRef<str> for SessionId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SessionId {
+    fn from(s: String) -> SessionId {
+        SessionId(s)
+    }
+}
+
+impl From<&str> for SessionId {
+    fn from(s: &str) -> SessionId {
+        SessionId(s.to_string())
+    }
+}
+
+/// Which of a session's processes to send a signal to
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub enum KillWho {
+    /// Only the session leader, usually the process that started the session
+    Leader,
+    /// Every process in the session
+    All,
+}
+
+impl KillWho {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KillWho::Leader => "leader",
+            KillWho::All => "all",
+        }
+    }
+}
+
+impl fmt::Display for KillWho {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A session currently known to logind, as reported by `Logind::list_sessions`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SessionInfo {
+    pub id: SessionId,
+    /// UID of the user owning the session
+    pub uid: u32,
+    /// Name of the user owning the session
+    pub user: String,
+    /// ID of the seat the session is on, or an empty string if it has no seat
+    pub seat: String,
+}
+
+/// Snapshot of a session's commonly-used properties, fetched in a single D-Bus call.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SessionProperties {
+    pub id: SessionId,
+    pub name: String,
+    /// UID of the user owning the session
+    pub user: u32,
+    /// ID of the seat the session is on, or an empty string if it has no seat
+    pub seat: String,
+    pub tty: String,
+    pub display: String,
+    pub vtnr: u32,
+    pub remote: bool,
+    pub service: String,
+    /// Session type, such as `x11`, `wayland`, or `tty`
+    pub session_type: String,
+    /// Session class, such as `user` or `greeter`
+    pub class: String,
+    pub active: bool,
+    /// Session state: `online`, `active`, or `closing`
+    pub state: String,
+    pub idle_hint: bool,
+    pub locked_hint: bool,
+}
+
+/// Handle to a logind session.
+///
+/// By default, every property read is a D-Bus call. A session created with
+/// [`with_cached_properties`](Session::with_cached_properties) instead keeps a snapshot of its
+/// properties, which `name()` and `id()` read from.
 pub struct Session<'a> {
     proxy: Proxy<'a, &'a Connection>,
+    cached: Option<SessionProperties>,
+    /// Dry-run setting of the `Logind` this came from, passed on to signal callbacks
+    dry_run: bool,
+}
+
+impl fmt::Debug for Session<'_> {
+    /// Shows the cached ID and name if available, and otherwise the session's object path, so that
+    /// formatting never makes a D-Bus call.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("Session");
+        match &self.cached {
+            Some(cached) => debug.field("id", &cached.id).field("name", &cached.name),
+            None => debug.field("path", &self.proxy.path),
+        };
+        debug.finish()
+    }
 }
 
 impl<'a> Session<'a> {
-    pub(crate) fn new(proxy: Proxy<'a, &'a Connection>) -> Session<'a> {
-        Session { proxy }
+    pub(crate) fn new(proxy: Proxy<'a, &'a Connection>, dry_run: bool) -> Session<'a> {
+        Session {
+            proxy,
+            cached: None,
+            dry_run,
+        }
+    }
+
+    /// Fetch the session's properties and cache them, so that `name()` and `id()` don't need to
+    /// make any further D-Bus calls. Use [`refresh`](Session::refresh) to update the cache.
+    pub fn with_cached_properties(mut self) -> Result<Session<'a>, LogindError> {
+        self.refresh()?;
+        Ok(self)
+    }
+
+    /// Re-fetch the cached properties. This also turns on caching if it wasn't already.
+    pub fn refresh(&mut self) -> Result<(), LogindError> {
+        self.cached = Some(self.properties()?);
+        Ok(())
+    }
+
+    /// The cached session properties, if caching is on.
+    pub fn cached_properties(&self) -> Option<&SessionProperties> {
+        self.cached.as_ref()
     }
 
     pub fn name(&self) -> Result<String, LogindError> {
+        if let Some(cached) = &self.cached {
+            return Ok(cached.name.clone());
+        }
         let name = self.proxy.name()?;
         Ok(name)
     }
 
     pub fn id(&self) -> Result<SessionId, LogindError> {
+        if let Some(cached) = &self.cached {
+            return Ok(cached.id.clone());
+        }
         let id = self.proxy.id()?;
         Ok(SessionId::new(id))
     }
 
+    /// UID of the user owning the session.
+    pub fn uid(&self) -> Result<u32, LogindError> {
+        if let Some(cached) = &self.cached {
+            return Ok(cached.user);
+        }
+        let (uid, _) = self.proxy.user()?;
+        Ok(uid)
+    }
+
+    /// ID of the seat the session is on, such as `seat0`, or `None` if it has no seat (like SSH
+    /// sessions).
+    pub fn seat(&self) -> Result<Option<String>, LogindError> {
+        let seat = match &self.cached {
+            Some(cached) => cached.seat.clone(),
+            None => self.proxy.seat()?.0,
+        };
+        if seat.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(seat))
+        }
+    }
+
+    /// The session's audit session ID, for matching it up with audit log entries.
+    pub fn audit(&self) -> Result<u32, LogindError> {
+        Ok(self.proxy.audit()?)
+    }
+
+    /// The PAM service that created the session, such as `sshd` or `gdm-password`.
+    pub fn service(&self) -> Result<String, LogindError> {
+        Ok(self.proxy.service()?)
+    }
+
+    /// When the session was created, or `None` if logind hasn't recorded it yet.
+    pub fn started_at(&self) -> Result<Option<SystemTime>, LogindError> {
+        let micros = self.proxy.timestamp()?;
+        Ok(from_micros(micros).map(|since_epoch| UNIX_EPOCH + since_epoch))
+    }
+
+    /// When the session was created, as time since boot on the `CLOCK_MONOTONIC` clock. This is
+    /// `None` if logind hasn't recorded it yet.
+    pub fn started_at_monotonic(&self) -> Result<Option<Duration>, LogindError> {
+        let micros = self.proxy.timestamp_monotonic()?;
+        Ok(from_micros(micros))
+    }
+
+    /// Fetch all commonly-used session properties at once. This only makes a single D-Bus call, so
+    /// it's cheaper than reading several properties individually. It always reads the current
+    /// values, even if caching is on.
+    pub fn properties(&self) -> Result<SessionProperties, LogindError> {
+        let props = self.proxy.get_all(SESSION_INTERFACE)?;
+        let user = properties::first_field(&props, "User")?
+            .as_u64()
+            .ok_or_else(|| LogindError::invalid_property("User"))?;
+        let seat = properties::first_field(&props, "Seat")?
+            .as_str()
+            .ok_or_else(|| LogindError::invalid_property("Seat"))?;
+
+        Ok(SessionProperties {
+            id: SessionId::new(properties::get(&props, "Id")?),
+            name: properties::get(&props, "Name")?,
+            user: user as u32,
+            seat: seat.to_string(),
+            tty: properties::get(&props, "TTY")?,
+            display: properties::get(&props, "Display")?,
+            vtnr: properties::get(&props, "VTNr")?,
+            remote: properties::get(&props, "Remote")?,
+            service: properties::get(&props, "Service")?,
+            session_type: properties::get(&props, "Type")?,
+            class: properties::get(&props, "Class")?,
+            active: properties::get(&props, "Active")?,
+            state: properties::get(&props, "State")?,
+            idle_hint: properties::get(&props, "IdleHint")?,
+            locked_hint: properties::get(&props, "LockedHint")?,
+        })
+    }
+
+    /// Checks if this is an active, local user session, which is what a screen locker should act
+    /// on. This is true when all of the following hold:
+    ///
+    /// * `Active` is true, meaning the session is in the foreground of its seat
+    /// * `Remote` is false, so it isn't an SSH or other remote login
+    /// * `Class` is `user`, rather than a greeter or lock screen session
+    ///
+    /// The properties are read with a single D-Bus call, ignoring any cached values.
+    pub fn is_active_and_local(&self) -> Result<bool, LogindError> {
+        let props = self.properties()?;
+        Ok(props.active && !props.remote && props.class == "user")
+    }
+
+    /// Checks if this session can be switched to with [`activate`](Session::activate). This is
+    /// true when all of the following hold:
+    ///
+    /// * `Remote` is false, since remote sessions have nothing to switch to
+    /// * The session is on a seat and has a virtual terminal (`VTNr` is nonzero)
+    /// * The seat supports running multiple sessions at once
+    ///
+    /// The session's properties are read with a single D-Bus call, ignoring any cached values.
+    pub fn can_activate(&self) -> Result<bool, LogindError> {
+        let props = self.properties()?;
+        if props.remote || props.seat.is_empty() || props.vtnr == 0 {
+            return Ok(false);
+        }
+        let seat = Logind::with_dry_run(self.proxy.connection, self.dry_run).seat(&props.seat)?;
+        seat.can_multi_session()
+    }
+
+    /// Bring this session to the foreground of its seat.
+    pub fn activate(&self) -> Result<(), LogindError> {
+        self.proxy.activate()?;
+        Ok(())
+    }
+
     /// Lock this session by sending a `Lock` signal
     pub fn lock(&self) -> Result<(), LogindError> {
         self.proxy.lock()?;
         Ok(())
     }
 
-    /// Register a callback to run when the session is locked.
-    pub fn on_lock<F: Fn(Logind) + Send + 'static>(&self, cb: F) -> Result<(), LogindError> {
+    /// Terminate the session, killing all of its processes.
+    pub fn terminate(&self) -> Result<(), LogindError> {
+        self.proxy.terminate()?;
+        Ok(())
+    }
+
+    /// Send a signal to the session's leader or to all of its processes.
+    pub fn kill(&self, who: KillWho, signal: i32) -> Result<(), LogindError> {
+        self.proxy.kill(who.as_str(), signal)?;
+        Ok(())
+    }
+
+    /// Register a callback to run when the session is locked. Errors returned by the callback are
+    /// passed to the error sink (see [`set_error_sink`](crate::set_error_sink)).
+    pub fn on_lock<F: Fn(Logind) -> CallbackResult + Send + 'static>(
+        &self,
+        cb: F,
+    ) -> Result<SignalToken<'a>, LogindError> {
+        let dry_run = self.dry_run;
         match self.proxy.match_signal(
             move |_: OrgFreedesktopLogin1SessionLock, conn: &Connection, _: &Message| {
-                cb(Logind::new(conn));
+                callback::report("Lock", cb(Logind::with_dry_run(conn, dry_run)));
                 true
             },
         ) {
-            Ok(_) => Ok(()),
+            Ok(token) => Ok(SignalToken::new(self.proxy.connection, token)),
             Err(e) => Err(LogindError::match_failed("Lock", e)),
         }
     }
 
-    /// Register a callback to run when the session is unlocked.
-    pub fn on_unlock<F: Fn(Logind) + Send + 'static>(
+    /// Register a callback to run when the session is unlocked. Errors returned by the callback are
+    /// passed to the error sink.
+    pub fn on_unlock<F: Fn(Logind) -> CallbackResult + Send + 'static>(
         &self,
         cb: F,
-    ) -> Result<(), LogindError> {
+    ) -> Result<SignalToken<'a>, LogindError> {
+        let dry_run = self.dry_run;
         match self.proxy.match_signal(
             move |_: OrgFreedesktopLogin1SessionUnlock, conn: &Connection, _: &Message| {
-                cb(Logind::new(conn));
+                callback::report("Unlock", cb(Logind::with_dry_run(conn, dry_run)));
                 true
             },
         ) {
-            Ok(_) => Ok(()),
+            Ok(token) => Ok(SignalToken::new(self.proxy.connection, token)),
             Err(e) => Err(LogindError::match_failed("Unlock", e)),
         }
     }
 
+    /// Take control of this session, allowing the caller to manage its devices with `take_device`.
+    ///
+    /// Only one controller can hold control of a session at a time. If `force` is true and the
+    /// caller is privileged, control is taken even if another process already has it. Control is
+    /// released automatically if the caller disconnects from the bus.
+    pub fn take_control(&self, force: bool) -> Result<(), LogindError> {
+        self.proxy.take_control(force)?;
+        Ok(())
+    }
+
+    /// Release control of this session, along with any devices taken through it.
+    pub fn release_control(&self) -> Result<(), LogindError> {
+        self.proxy.release_control()?;
+        Ok(())
+    }
+
+    /// Open the device with the given major and minor numbers on behalf of this session. The caller
+    /// must have taken control of the session with `take_control` first.
+    ///
+    /// Returns the device file descriptor and whether the device is currently inactive (paused),
+    /// for example because the session is not in the foreground.
+    pub fn take_device(&self, major: u32, minor: u32) -> Result<(DeviceFd, bool), LogindError> {
+        let (fd, inactive) = self.proxy.take_device(major, minor)?;
+        Ok((DeviceFd::new(major, minor, fd), inactive))
+    }
+
+    /// Release a device previously taken with `take_device`.
+    pub fn release_device(&self, major: u32, minor: u32) -> Result<(), LogindError> {
+        self.proxy.release_device(major, minor)?;
+        Ok(())
+    }
+
+    /// Acknowledge that a device paused with `PauseKind::Pause` has been released by the
+    /// controller. logind waits for this before completing the pause.
+    pub fn pause_device_complete(&self, major: u32, minor: u32) -> Result<(), LogindError> {
+        self.proxy.pause_device_complete(major, minor)?;
+        Ok(())
+    }
+
+    /// Register a callback to run when logind pauses one of this session's devices. The callback
+    /// receives the device major and minor numbers and why the device was paused.
+    pub fn on_pause_device<F: Fn(u32, u32, PauseKind) + Send + 'static>(
+        &self,
+        cb: F,
+    ) -> Result<SignalToken<'a>, LogindError> {
+        match self.proxy.match_signal(
+            move |signal: OrgFreedesktopLogin1SessionPauseDevice, _: &Connection, _: &Message| {
+                cb(
+                    signal.arg0,
+                    signal.arg1,
+                    PauseKind::from_signal(&signal.arg2),
+                );
+                true
+            },
+        ) {
+            Ok(token) => Ok(SignalToken::new(self.proxy.connection, token)),
+            Err(e) => Err(LogindError::match_failed("PauseDevice", e)),
+        }
+    }
+
+    /// Register a callback to run when logind resumes one of this session's devices. The callback
+    /// receives the device major and minor numbers and a new file descriptor for the device, which
+    /// it takes ownership of.
+    pub fn on_resume_device<F: Fn(u32, u32, RawFd) + Send + 'static>(
+        &self,
+        cb: F,
+    ) -> Result<SignalToken<'a>, LogindError> {
+        match self.proxy.match_signal(
+            move |signal: OrgFreedesktopLogin1SessionResumeDevice, _: &Connection, _: &Message| {
+                cb(signal.arg0, signal.arg1, signal.arg2.into_fd());
+                true
+            },
+        ) {
+            Ok(token) => Ok(SignalToken::new(self.proxy.connection, token)),
+            Err(e) => Err(LogindError::match_failed("ResumeDevice", e)),
+        }
+    }
+
+    /// Gets the locked hint for the session, which screen lockers set while the session is locked.
+    pub fn locked_hint(&self) -> Result<bool, LogindError> {
+        Ok(self.proxy.locked_hint()?)
+    }
+
+    /// Sets the session locked hint.
+    pub fn set_locked_hint(&self, locked: bool) -> Result<(), LogindError> {
+        self.proxy.set_locked_hint_(locked)?;
+        Ok(())
+    }
+
     /// Gets the idle hint for the session.
     pub fn idle_hint(&self) -> Result<bool, LogindError> {
         Ok(self.proxy.idle_hint()?)
     }
 
+    /// When the idle hint last changed, or `None` if it never has. While the session is idle, this
+    /// is when it went idle.
+    pub fn idle_since(&self) -> Result<Option<SystemTime>, LogindError> {
+        let micros = self.proxy.idle_since_hint()?;
+        Ok(from_micros(micros).map(|since_epoch| UNIX_EPOCH + since_epoch))
+    }
+
     /// Sets the session idle hint.
     pub fn set_idle_hint(&self, idle: bool) -> Result<(), LogindError> {
         self.proxy.set_idle_hint_(idle)?;
         Ok(())
     }
 }
+
+/// Converts a logind timestamp in microseconds, where 0 means unset.
+fn from_micros(micros: u64) -> Option<Duration> {
+    if micros == 0 {
+        None
+    } else {
+        Some(Duration::from_micros(micros))
+    }
+}
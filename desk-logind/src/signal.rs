@@ -0,0 +1,43 @@
+//! Signal subscription handles
+
+use dbus::channel::Token;
+
+use crate::error::LogindError;
+use crate::transport::LogindTransport;
+
+/// Handle on a D-Bus signal subscription, returned by the `on_*` callback registration methods.
+/// The subscription is removed, and its callback dropped, when this is dropped.
+#[must_use = "the signal subscription is removed when the token is dropped"]
+pub struct SignalToken<'a> {
+    transport: &'a dyn LogindTransport,
+    token: Option<Token>,
+}
+
+impl<'a> SignalToken<'a> {
+    pub(crate) fn new(transport: &'a dyn LogindTransport, token: Token) -> SignalToken<'a> {
+        SignalToken {
+            transport,
+            token: Some(token),
+        }
+    }
+
+    /// Remove the subscription, reporting any error. Dropping the token does the same thing, but
+    /// ignores errors.
+    pub fn remove(mut self) -> Result<(), LogindError> {
+        match self.token.take() {
+            Some(token) => {
+                self.transport.remove_match(token)?;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SignalToken<'_> {
+    fn drop(&mut self) {
+        if let Some(token) = self.token.take() {
+            let _ = self.transport.remove_match(token);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Abstraction over the D-Bus connection used to talk to logind
+use dbus::blocking::{BlockingSender, Connection};
+use dbus::channel::Token;
+
+/// The D-Bus operations `Logind` needs: blocking method calls (via `BlockingSender`) and removing
+/// signal subscriptions. This is implemented by `dbus::blocking::Connection`, and can be
+/// implemented by test doubles that return canned replies instead of talking to a real bus.
+///
+/// Signal subscriptions themselves are only supported on a real `Connection`, since the callbacks
+/// are handed a `Connection` to build a `Logind` from.
+pub trait LogindTransport: BlockingSender {
+    /// Remove a signal subscription previously added to this transport.
+    fn remove_match(&self, token: Token) -> Result<(), dbus::Error>;
+}
+
+impl LogindTransport for Connection {
+    fn remove_match(&self, token: Token) -> Result<(), dbus::Error> {
+        Connection::remove_match(self, token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::time::Duration;
+
+    use dbus::Message;
+
+    use super::*;
+    use crate::{Logind, LogindError, PowerAction, PowerCapability, MANAGER_INTERFACE};
+
+    type Reply = Result<Message, dbus::Error>;
+
+    /// Transport that answers method calls with canned replies and records what was called
+    struct FakeTransport {
+        /// Called with each method call, returning the reply to send
+        reply: Box<dyn Fn(&Message) -> Reply>,
+        calls: RefCell<Vec<(String, String)>>,
+    }
+
+    impl FakeTransport {
+        fn new<F: Fn(&Message) -> Reply + 'static>(reply: F) -> FakeTransport {
+            FakeTransport {
+                reply: Box::new(reply),
+                calls: RefCell::new(Vec::new()),
+            }
+        }
+
+        /// The interface and member of each method called so far
+        fn calls(&self) -> Vec<(String, String)> {
+            self.calls.borrow().clone()
+        }
+    }
+
+    impl BlockingSender for FakeTransport {
+        fn send_with_reply_and_block(&self, mut msg: Message, _timeout: Duration) -> Reply {
+            // Sending assigns a serial, which replies need to refer to
+            msg.set_serial(1);
+            self.calls.borrow_mut().push((
+                msg.interface().unwrap().to_string(),
+                msg.member().unwrap().to_string(),
+            ));
+            (self.reply)(&msg)
+        }
+    }
+
+    impl LogindTransport for FakeTransport {
+        fn remove_match(&self, _token: Token) -> Result<(), dbus::Error> {
+            // Nothing subscribes to signals through the fake, so there's nothing to remove
+            Err(dbus::Error::new_custom(
+                "org.freedesktop.DBus.Error.MatchRuleNotFound",
+                "No signal subscriptions on a fake transport",
+            ))
+        }
+    }
+
+    fn manager_call(member: &str) -> (String, String) {
+        (MANAGER_INTERFACE.to_string(), member.to_string())
+    }
+
+    #[test]
+    fn power_calls_manager_method() {
+        let transport = FakeTransport::new(|msg| {
+            let (interactive,): (bool,) = msg.read_all().unwrap();
+            assert!(interactive);
+            Ok(msg.method_return())
+        });
+        let logind = Logind::new(&transport);
+
+        logind
+            .power(PowerAction::SuspendThenHibernate, true)
+            .unwrap();
+        logind.reboot(true).unwrap();
+        assert_eq!(
+            transport.calls(),
+            [manager_call("SuspendThenHibernate"), manager_call("Reboot")]
+        );
+    }
+
+    #[test]
+    fn power_maps_errors() {
+        let transport = FakeTransport::new(|_| {
+            Err(dbus::Error::new_custom(
+                "org.freedesktop.DBus.Error.AccessDenied",
+                "Access denied",
+            ))
+        });
+        let logind = Logind::new(&transport);
+
+        let err = logind.power_off(false).unwrap_err();
+        assert!(err.is_not_authorized(), "unexpected error: {:?}", err);
+    }
+
+    #[test]
+    fn can_power_parses_result() {
+        let transport = FakeTransport::new(|msg| {
+            let result = match msg.member().unwrap().as_ref() {
+                "CanSuspend" => "yes",
+                "CanHibernate" => "na",
+                "CanReboot" => "challenge",
+                _ => "maybe",
+            };
+            Ok(msg.method_return().append1(result))
+        });
+        let logind = Logind::new(&transport);
+
+        assert_eq!(logind.can_suspend().unwrap(), PowerCapability::Yes);
+        assert_eq!(logind.can_hibernate().unwrap(), PowerCapability::Na);
+        assert_eq!(
+            logind.can_power(PowerAction::Reboot).unwrap(),
+            PowerCapability::Challenge
+        );
+        assert_eq!(
+            logind.can_halt().unwrap(),
+            PowerCapability::Other("maybe".to_string())
+        );
+        assert_eq!(
+            transport.calls(),
+            [
+                manager_call("CanSuspend"),
+                manager_call("CanHibernate"),
+                manager_call("CanReboot"),
+                manager_call("CanHalt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_power_rejects_bad_reply() {
+        // A reply without the expected string argument
+        let transport = FakeTransport::new(|msg| Ok(msg.method_return()));
+        let logind = Logind::new(&transport);
+
+        assert!(matches!(
+            logind.can_suspend(),
+            Err(LogindError::DBusError { .. })
+        ));
+    }
+
+    #[test]
+    fn dry_run_skips_power_calls() {
+        let transport = FakeTransport::new(|_| panic!("dry run called logind"));
+        let mut logind = Logind::new(&transport);
+        assert!(!logind.is_dry_run());
+
+        logind.set_dry_run(true);
+        assert!(logind.is_dry_run());
+        for &action in PowerAction::ALL.iter() {
+            logind.power(action, false).unwrap();
+        }
+        assert!(transport.calls().is_empty());
+    }
+}
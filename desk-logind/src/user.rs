@@ -0,0 +1,108 @@
+use std::fmt;
+
+use dbus::blocking::{Connection, Proxy};
+
+use crate::api::user::OrgFreedesktopLogin1User;
+use crate::error::LogindError;
+use crate::session::SessionId;
+
+/// A user currently known to logind, as reported by `Logind::list_users`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UserInfo {
+    pub uid: u32,
+    pub name: String,
+}
+
+/// State of a user known to logind
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub enum UserState {
+    /// The user is not logged in
+    Offline,
+    /// The user is not logged in, but has lingering enabled so their services keep running
+    Lingering,
+    /// The user is logged in, but none of their sessions are active
+    Online,
+    /// The user is logged in and has at least one active session
+    Active,
+    /// The user is logging out
+    Closing,
+    /// A state this library doesn't know about
+    Other(String),
+}
+
+impl UserState {
+    fn from_property(state: &str) -> UserState {
+        match state {
+            "offline" => UserState::Offline,
+            "lingering" => UserState::Lingering,
+            "online" => UserState::Online,
+            "active" => UserState::Active,
+            "closing" => UserState::Closing,
+            other => UserState::Other(other.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            UserState::Offline => "offline",
+            UserState::Lingering => "lingering",
+            UserState::Online => "online",
+            UserState::Active => "active",
+            UserState::Closing => "closing",
+            UserState::Other(state) => state,
+        }
+    }
+}
+
+impl fmt::Display for UserState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Handle to a logind user
+pub struct User<'a> {
+    proxy: Proxy<'a, &'a Connection>,
+}
+
+impl<'a> User<'a> {
+    pub(crate) fn new(proxy: Proxy<'a, &'a Connection>) -> User<'a> {
+        User { proxy }
+    }
+
+    pub fn uid(&self) -> Result<u32, LogindError> {
+        Ok(self.proxy.uid()?)
+    }
+
+    pub fn name(&self) -> Result<String, LogindError> {
+        let name = self.proxy.name()?;
+        Ok(name)
+    }
+
+    /// Gets the current state of the user.
+    pub fn state(&self) -> Result<UserState, LogindError> {
+        let state = self.proxy.state()?;
+        Ok(UserState::from_property(&state))
+    }
+
+    /// Gets the IDs of all of this user's sessions.
+    pub fn sessions(&self) -> Result<Vec<SessionId>, LogindError> {
+        let sessions = self.proxy.sessions()?;
+        Ok(sessions
+            .into_iter()
+            .map(|(id, _)| SessionId::new(id))
+            .collect())
+    }
+
+    /// Terminate all of this user's sessions and processes.
+    pub fn terminate(&self) -> Result<(), LogindError> {
+        self.proxy.terminate()?;
+        Ok(())
+    }
+
+    /// Send a signal to all of this user's processes.
+    pub fn kill(&self, signal: i32) -> Result<(), LogindError> {
+        self.proxy.kill(signal)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,366 @@
+//! A stub logind on a private D-Bus daemon, so that tests can exercise `Logind` without touching
+//! the real system bus. It has a couple of canned sessions, keeps track of inhibitor locks, and
+//! can send logind's signals on request.
+//!
+//! This needs `dbus-daemon` to be installed. If it isn't, `MockLogind::start` returns `None` and
+//! tests should skip themselves. Set `DESK_REQUIRE_DBUS` (such as in CI) to fail instead, so that
+//! a missing `dbus-daemon` can't quietly skip every test.
+
+use std::env;
+use std::ffi::CString;
+use std::io::{BufRead, BufReader};
+use std::os::unix::io::RawFd;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use dbus::arg::OwnedFd;
+use dbus::blocking::Connection;
+use dbus::channel::{Channel, MatchingReceiver, Sender as _};
+use dbus::message::MatchRule;
+use dbus::strings::ErrorName;
+use dbus::{Message, Path};
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::unistd::{close, pipe, read};
+
+const SERVICE_NAME: &str = "org.freedesktop.login1";
+const MANAGER_PATH: &str = "/org/freedesktop/login1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+/// A session the mock reports, as `(id, uid, user, seat)`. The first one is the caller's own
+/// session.
+pub const SESSIONS: &[(&str, u32, &str, &str)] =
+    &[("1", 1000, "alice", "seat0"), ("c2", 1001, "bob", "")];
+
+/// PID the mock reports for every inhibitor lock
+pub const INHIBITOR_PID: u32 = 4242;
+
+/// An inhibitor lock taken through the mock
+struct Inhibitor {
+    what: String,
+    who: String,
+    why: String,
+    mode: String,
+    uid: u32,
+    /// Read end of the pipe whose write end was handed to the client. It reaches end-of-file once
+    /// the client closes its end, releasing the lock.
+    fd: RawFd,
+}
+
+type Inhibitors = Arc<Mutex<Vec<Inhibitor>>>;
+
+pub struct MockLogind {
+    daemon: Child,
+    address: String,
+    signals: Sender<Message>,
+    stop: Arc<AtomicBool>,
+    server: Option<JoinHandle<()>>,
+}
+
+impl MockLogind {
+    /// Start a private bus with the stub logind on it, or return `None` if `dbus-daemon` isn't
+    /// available. Panics instead if `DESK_REQUIRE_DBUS` is set.
+    pub fn start() -> Option<MockLogind> {
+        let mut daemon = match Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(daemon) => daemon,
+            Err(e) if env::var_os("DESK_REQUIRE_DBUS").is_some() => {
+                panic!("Could not start dbus-daemon: {}", e)
+            }
+            Err(e) => {
+                eprintln!("Could not start dbus-daemon, skipping test: {}", e);
+                return None;
+            }
+        };
+
+        let mut address = String::new();
+        let stdout = daemon
+            .stdout
+            .take()
+            .expect("dbus-daemon stdout not captured");
+        BufReader::new(stdout)
+            .read_line(&mut address)
+            .expect("Could not read dbus-daemon address");
+        let address = address.trim().to_string();
+
+        // Claim the name before returning, so that tests can't call logind before it exists
+        let conn = connect(&address);
+        conn.request_name(SERVICE_NAME, false, false, true)
+            .expect("Could not claim org.freedesktop.login1");
+
+        let (signals, outgoing) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let server = {
+            let stop = stop.clone();
+            thread::spawn(move || serve(conn, outgoing, stop))
+        };
+
+        Some(MockLogind {
+            daemon,
+            address,
+            signals,
+            stop,
+            server: Some(server),
+        })
+    }
+
+    /// Open a new client connection to the private bus.
+    pub fn connect(&self) -> Connection {
+        connect(&self.address)
+    }
+
+    /// Send `PrepareForSleep`, as logind does before sleeping (`true`) and after resuming
+    /// (`false`).
+    pub fn prepare_for_sleep(&self, start: bool) {
+        self.emit(MANAGER_PATH, MANAGER_INTERFACE, "PrepareForSleep", start);
+    }
+
+    /// Send `PrepareForShutdown`, as logind does before shutting down (`true`) or if a shutdown
+    /// is cancelled (`false`).
+    pub fn prepare_for_shutdown(&self, start: bool) {
+        self.emit(MANAGER_PATH, MANAGER_INTERFACE, "PrepareForShutdown", start);
+    }
+
+    /// Ask a session to lock its screen.
+    pub fn lock_session(&self, id: &str) {
+        let signal = Message::signal(&session_path(id), &SESSION_INTERFACE.into(), &"Lock".into());
+        self.signals.send(signal).unwrap();
+    }
+
+    fn emit(&self, path: &str, interface: &str, member: &str, arg: bool) {
+        let signal = Message::signal(&path.into(), &interface.into(), &member.into()).append1(arg);
+        self.signals.send(signal).unwrap();
+    }
+}
+
+impl Drop for MockLogind {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(server) = self.server.take() {
+            let _ = server.join();
+        }
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// Process messages on `conn` until `done` returns true, panicking if that takes too long.
+pub fn process_until<F: FnMut() -> bool>(conn: &Connection, mut done: F) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(Instant::now() < deadline, "Timed out waiting for logind");
+        conn.process(Duration::from_millis(10)).unwrap();
+    }
+}
+
+/// Wait until `done` returns true, panicking if that takes too long. Unlike `process_until`, this
+/// doesn't process any messages on the client side.
+pub fn wait_until<F: FnMut() -> bool>(mut done: F) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(Instant::now() < deadline, "Timed out waiting for logind");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn connect(address: &str) -> Connection {
+    let mut channel = Channel::open_private(address).expect("Could not connect to private bus");
+    channel
+        .register()
+        .expect("Could not register on private bus");
+    Connection::from(channel)
+}
+
+/// The mock's main loop: answer method calls, send queued signals, and drop released inhibitors.
+fn serve(conn: Connection, outgoing: Receiver<Message>, stop: Arc<AtomicBool>) {
+    let inhibitors: Inhibitors = Arc::new(Mutex::new(Vec::new()));
+    {
+        let inhibitors = inhibitors.clone();
+        conn.start_receive(
+            MatchRule::new_method_call(),
+            Box::new(move |call, conn| {
+                let reply = handle(&call, &inhibitors).unwrap_or_else(|(name, text)| {
+                    call.error(&ErrorName::new(name).unwrap(), &CString::new(text).unwrap())
+                });
+                let _ = conn.send(reply);
+                true
+            }),
+        );
+    }
+
+    while !stop.load(Ordering::SeqCst) {
+        conn.process(Duration::from_millis(10)).unwrap();
+        for signal in outgoing.try_iter() {
+            let _ = conn.send(signal);
+        }
+        conn.channel().flush();
+        reap_inhibitors(&inhibitors);
+    }
+}
+
+/// Error reply to a method call, as a D-Bus error name and message
+type CallError = (&'static str, String);
+
+fn handle(call: &Message, inhibitors: &Inhibitors) -> Result<Message, CallError> {
+    let interface = call.interface();
+    let member = call.member();
+    let path = call.path();
+    let target = (
+        interface.as_deref().unwrap_or(""),
+        member.as_deref().unwrap_or(""),
+    );
+    let on_manager = path.as_deref() == Some(MANAGER_PATH);
+    match target {
+        (MANAGER_INTERFACE, "ListSessions") => {
+            let sessions: Vec<_> = SESSIONS
+                .iter()
+                .map(|&(id, uid, user, seat)| {
+                    (
+                        id.to_string(),
+                        uid,
+                        user.to_string(),
+                        seat.to_string(),
+                        session_path(id),
+                    )
+                })
+                .collect();
+            Ok(call.method_return().append1(sessions))
+        }
+        (MANAGER_INTERFACE, "GetSession") => {
+            let id: &str = call.read1().map_err(invalid_args)?;
+            let (id, ..) = find_session(id)?;
+            Ok(call.method_return().append1(session_path(id)))
+        }
+        (MANAGER_INTERFACE, "GetSessionByPID") => {
+            let (id, ..) = SESSIONS[0];
+            Ok(call.method_return().append1(session_path(id)))
+        }
+        (MANAGER_INTERFACE, "Inhibit") => {
+            let (what, who, why, mode): (&str, &str, &str, &str) =
+                call.read4().map_err(invalid_args)?;
+            if what.is_empty() || (mode != "block" && mode != "delay") {
+                return Err(invalid_args(format!("Invalid inhibitor {} {}", what, mode)));
+            }
+
+            let (read_end, write_end) = pipe().expect("Could not create inhibitor pipe");
+            fcntl(read_end, FcntlArg::F_SETFL(OFlag::O_NONBLOCK)).unwrap();
+            inhibitors.lock().unwrap().push(Inhibitor {
+                what: what.to_string(),
+                who: who.to_string(),
+                why: why.to_string(),
+                mode: mode.to_string(),
+                uid: SESSIONS[0].1,
+                fd: read_end,
+            });
+            // Safety: the write end was just created, and nothing else owns it. Appending it
+            // duplicates the fd, so the mock's copy is closed once the reply is built.
+            let fd = unsafe { OwnedFd::new(write_end) };
+            Ok(call.method_return().append1(fd))
+        }
+        (MANAGER_INTERFACE, "ListInhibitors") => {
+            let listed: Vec<_> = inhibitors
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|i| {
+                    (
+                        i.what.clone(),
+                        i.who.clone(),
+                        i.why.clone(),
+                        i.mode.clone(),
+                        i.uid,
+                        INHIBITOR_PID,
+                    )
+                })
+                .collect();
+            Ok(call.method_return().append1(listed))
+        }
+        (PROPERTIES_INTERFACE, "Get") if on_manager => {
+            let (interface, property): (&str, &str) = call.read2().map_err(invalid_args)?;
+            let mode = match (interface, property) {
+                (MANAGER_INTERFACE, "BlockInhibited") => "block",
+                (MANAGER_INTERFACE, "DelayInhibited") => "delay",
+                _ => {
+                    return Err((
+                        "org.freedesktop.DBus.Error.UnknownProperty",
+                        format!("Unknown property {}", property),
+                    ))
+                }
+            };
+            let inhibited = inhibitors
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|i| i.mode == mode)
+                .map(|i| i.what.as_str())
+                .collect::<Vec<_>>()
+                .join(":");
+            Ok(call.method_return().append1(dbus::arg::Variant(inhibited)))
+        }
+        (interface, member) => Err((
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            format!("The mock doesn't implement {}.{}", interface, member),
+        )),
+    }
+}
+
+/// Look up a canned session. Like logind, the empty ID means the caller's session, and so does
+/// `XDG_SESSION_ID`, since `Logind::current_session` looks that up if it's set.
+fn find_session(id: &str) -> Result<(&'static str, u32, &'static str, &'static str), CallError> {
+    let own_id = std::env::var("XDG_SESSION_ID").ok();
+    if id.is_empty() || own_id.as_deref() == Some(id) {
+        return Ok(SESSIONS[0]);
+    }
+    SESSIONS
+        .iter()
+        .copied()
+        .find(|&(session, ..)| session == id)
+        .ok_or_else(|| {
+            (
+                "org.freedesktop.login1.NoSuchSession",
+                format!("No session '{}' known", id),
+            )
+        })
+}
+
+/// Drop inhibitors whose client end has been closed.
+fn reap_inhibitors(inhibitors: &Inhibitors) {
+    let mut buf = [0; 16];
+    inhibitors.lock().unwrap().retain(|inhibitor| {
+        // Nothing is ever written, so a successful read means end-of-file
+        match read(inhibitor.fd, &mut buf) {
+            Ok(_) => {
+                let _ = close(inhibitor.fd);
+                false
+            }
+            Err(_) => true,
+        }
+    });
+}
+
+/// Object path logind uses for a session. Like `sd_bus_path_encode`, anything besides letters
+/// and digits, and a leading digit, is escaped as `_` followed by two hex digits.
+fn session_path(id: &str) -> Path<'static> {
+    let mut path = format!("{}/session/", MANAGER_PATH);
+    for (i, byte) in id.bytes().enumerate() {
+        if byte.is_ascii_alphabetic() || (byte.is_ascii_digit() && i > 0) {
+            path.push(byte as char);
+        } else {
+            path.push_str(&format!("_{:02x}", byte));
+        }
+    }
+    Path::new(path).unwrap()
+}
+
+fn invalid_args<E: ToString>(e: E) -> CallError {
+    ("org.freedesktop.DBus.Error.InvalidArgs", e.to_string())
+}
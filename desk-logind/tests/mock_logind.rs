@@ -0,0 +1,136 @@
+//! Tests against a stub logind on a private bus (see `mock/mod.rs`)
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use desk_logind::inhibitor::{InhibitEvent, InhibitEventSet, InhibitMode};
+use desk_logind::{Logind, LogindEvent, SessionId, SessionInfo};
+
+use crate::mock::{process_until, wait_until, MockLogind, INHIBITOR_PID, SESSIONS};
+
+mod mock;
+
+/// Start the mock, or skip the test if there's no `dbus-daemon` (unless `DESK_REQUIRE_DBUS` is set)
+macro_rules! start_mock {
+    () => {
+        match MockLogind::start() {
+            Some(mock) => mock,
+            None => return,
+        }
+    };
+}
+
+#[test]
+fn lists_sessions() {
+    let mock = start_mock!();
+    let conn = mock.connect();
+    let logind = Logind::new(&conn);
+
+    let expected: Vec<_> = SESSIONS
+        .iter()
+        .map(|&(id, uid, user, seat)| SessionInfo {
+            id: SessionId::from(id),
+            uid,
+            user: user.to_string(),
+            seat: seat.to_string(),
+        })
+        .collect();
+    assert_eq!(logind.list_sessions().unwrap(), expected);
+}
+
+#[test]
+fn unknown_session_is_no_such_session() {
+    let mock = start_mock!();
+    let conn = mock.connect();
+    let logind = Logind::new(&conn);
+
+    let err = logind.session(&SessionId::from("missing")).unwrap_err();
+    assert!(err.is_no_such_session(), "unexpected error: {:?}", err);
+}
+
+#[test]
+fn inhibitor_lock_is_held_until_released() {
+    let mock = start_mock!();
+    let conn = mock.connect();
+    let logind = Logind::new(&conn);
+
+    let events = InhibitEventSet::with_event(InhibitEvent::Sleep);
+    let lock = logind
+        .inhibit("mock test", "Testing", &events, InhibitMode::Delay)
+        .unwrap();
+
+    let inhibitors = logind.list_inhibitors().unwrap();
+    assert_eq!(inhibitors.len(), 1);
+    assert_eq!(inhibitors[0].what, events);
+    assert_eq!(inhibitors[0].who, "mock test");
+    assert_eq!(inhibitors[0].why, "Testing");
+    assert_eq!(inhibitors[0].mode, InhibitMode::Delay);
+    assert_eq!(inhibitors[0].pid, INHIBITOR_PID);
+    assert!(logind
+        .delay_inhibited()
+        .unwrap()
+        .contains(InhibitEvent::Sleep));
+    assert!(logind.block_inhibited().unwrap().is_empty());
+
+    lock.release().unwrap();
+    wait_until(|| logind.list_inhibitors().unwrap().is_empty());
+}
+
+#[test]
+fn sleep_signal_runs_callbacks() {
+    let mock = start_mock!();
+    let conn = mock.connect();
+    let logind = Logind::new(&conn);
+
+    let (sender, receiver) = mpsc::channel();
+    let resume_sender = sender.clone();
+    let _token = logind
+        .on_sleep(
+            move |_| Ok(sender.send("sleep")?),
+            move |_| Ok(resume_sender.send("resume")?),
+        )
+        .unwrap();
+
+    mock.prepare_for_sleep(true);
+    let mut received = Vec::new();
+    process_until(&conn, || {
+        received.extend(receiver.try_iter());
+        !received.is_empty()
+    });
+    mock.prepare_for_sleep(false);
+    process_until(&conn, || {
+        received.extend(receiver.try_iter());
+        received.len() == 2
+    });
+    assert_eq!(received, ["sleep", "resume"]);
+}
+
+#[test]
+fn events_are_normalized() {
+    let mock = start_mock!();
+    let conn = mock.connect();
+    let logind = Logind::new(&conn);
+    let mut events = logind.events().unwrap();
+
+    mock.prepare_for_sleep(true);
+    mock.lock_session(SESSIONS[0].0);
+    mock.prepare_for_shutdown(true);
+    mock.prepare_for_shutdown(false);
+
+    let mut received = Vec::new();
+    while received.len() < 4 {
+        match events.next_timeout(Duration::from_secs(5)).unwrap() {
+            Some(event) => received.push(event),
+            None => panic!("Timed out waiting for events, got {:?}", received),
+        }
+    }
+    assert_eq!(
+        received,
+        [
+            LogindEvent::SleepStart,
+            LogindEvent::SessionLocked,
+            LogindEvent::ShutdownScheduled,
+            LogindEvent::ShutdownCancelled,
+        ]
+    );
+}